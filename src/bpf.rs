@@ -0,0 +1,254 @@
+use crate::spec::DeviceRule;
+use crate::syscall::{bpf_prog_attach, bpf_prog_load, BpfInsn, BPF_CGROUP_DEVICE, BPF_PROG_TYPE_CGROUP_DEVICE};
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+
+// opcodes, see include/uapi/linux/bpf.h
+const LDX_MEM_W: u8 = 0x61;
+const ALU32_AND_K: u8 = 0x54;
+const ALU32_RSH_K: u8 = 0x74;
+const ALU32_MOV_X: u8 = 0xbc;
+const ALU64_MOV_K: u8 = 0xb7;
+const JMP32_JEQ_K: u8 = 0x16;
+const JMP32_JNE_K: u8 = 0x56;
+const JMP32_JNE_X: u8 = 0x5e;
+const JMP32_CLASS: u8 = 0x06;
+const JMP_EXIT: u8 = 0x95;
+
+// the fields of struct bpf_cgroup_dev_ctx the program gets in r1
+const CTX_ACCESS_TYPE: i16 = 0;
+const CTX_MAJOR: i16 = 4;
+const CTX_MINOR: i16 = 8;
+
+const DEVCG_DEV_BLOCK: i32 = 1;
+const DEVCG_DEV_CHAR: i32 = 2;
+const DEVCG_ACC_MKNOD: i32 = 1;
+const DEVCG_ACC_READ: i32 = 2;
+const DEVCG_ACC_WRITE: i32 = 4;
+const DEVCG_ACC_ALL: i32 = DEVCG_ACC_MKNOD | DEVCG_ACC_READ | DEVCG_ACC_WRITE;
+
+// registers holding the device type, the requested access, major and minor while the rules are checked
+const R_TYPE: u8 = 2;
+const R_ACCESS: u8 = 3;
+const R_MAJOR: u8 = 4;
+const R_MINOR: u8 = 5;
+
+// compiles the rules into a BPF_PROG_TYPE_CGROUP_DEVICE program and attaches it to the cgroup directory
+// the attachment keeps the program loaded, the fd is closed when we return
+pub fn attach_device_filter(cgroup_dir: BorrowedFd, rules: &[DeviceRule]) -> io::Result<()> {
+    let insns = device_filter(rules)?;
+    let prog = bpf_prog_load(BPF_PROG_TYPE_CGROUP_DEVICE, &insns, c"GPL")?;
+    bpf_prog_attach(cgroup_dir, prog.as_fd(), BPF_CGROUP_DEVICE)?;
+    Ok(())
+}
+
+// the rules are checked from last to first and the first match decides, anything no rule matches is denied
+pub fn device_filter(rules: &[DeviceRule]) -> io::Result<Vec<BpfInsn>> {
+    let mut insns = vec![
+        insn(LDX_MEM_W, R_TYPE, 1, CTX_ACCESS_TYPE, 0),
+        insn(ALU32_AND_K, R_TYPE, 0, 0, 0xffff),
+        insn(LDX_MEM_W, R_ACCESS, 1, CTX_ACCESS_TYPE, 0),
+        insn(ALU32_RSH_K, R_ACCESS, 0, 0, 16),
+        insn(LDX_MEM_W, R_MAJOR, 1, CTX_MAJOR, 0),
+        insn(LDX_MEM_W, R_MINOR, 1, CTX_MINOR, 0),
+    ];
+    for rule in rules.iter().rev() {
+        insns.extend(rule_block(rule)?);
+    }
+    insns.push(insn(ALU64_MOV_K, 0, 0, 0, 0));
+    insns.push(insn(JMP_EXIT, 0, 0, 0, 0));
+
+    Ok(insns)
+}
+
+// a block of conditional jumps past its end, followed by returning the verdict when none of them is taken
+fn rule_block(rule: &DeviceRule) -> io::Result<Vec<BpfInsn>> {
+    let mut block = vec![];
+
+    match rule.dev_type.as_deref().unwrap_or("a") {
+        "a" => (),
+        "c" => block.push(insn(JMP32_JNE_K, R_TYPE, 0, 0, DEVCG_DEV_CHAR)),
+        "b" => block.push(insn(JMP32_JNE_K, R_TYPE, 0, 0, DEVCG_DEV_BLOCK)),
+        t => return Err(io::Error::other(format!("unknown device rule type {}", t))),
+    }
+
+    let access = parse_access(rule.access.as_deref().unwrap_or("rwm"))?;
+    if access != DEVCG_ACC_ALL || !rule.allow {
+        block.push(insn(ALU32_MOV_X, 1, R_ACCESS, 0, 0));
+        block.push(insn(ALU32_AND_K, 1, 0, 0, access));
+        if rule.allow {
+            // an allow rule has to cover everything that is requested
+            block.push(insn(JMP32_JNE_X, 1, R_ACCESS, 0, 0));
+        } else {
+            // a deny rule applies as soon as it covers part of the request
+            block.push(insn(JMP32_JEQ_K, 1, 0, 0, 0));
+        }
+    }
+
+    if let Some(major) = rule.major.filter(|&major| major >= 0) {
+        block.push(insn(JMP32_JNE_K, R_MAJOR, 0, 0, major as i32));
+    }
+    if let Some(minor) = rule.minor.filter(|&minor| minor >= 0) {
+        block.push(insn(JMP32_JNE_K, R_MINOR, 0, 0, minor as i32));
+    }
+
+    let jumps = block.len();
+    block.push(insn(ALU64_MOV_K, 0, 0, 0, rule.allow as i32));
+    block.push(insn(JMP_EXIT, 0, 0, 0, 0));
+
+    // offsets are relative to the next instruction
+    let len = block.len();
+    for (i, jump) in block.iter_mut().take(jumps).enumerate() {
+        if jump.code & 0x07 == JMP32_CLASS {
+            jump.off = (len - i - 1) as i16;
+        }
+    }
+
+    Ok(block)
+}
+
+fn parse_access(access: &str) -> io::Result<i32> {
+    access.chars().try_fold(0, |bits, c| match c {
+        'r' => Ok(bits | DEVCG_ACC_READ),
+        'w' => Ok(bits | DEVCG_ACC_WRITE),
+        'm' => Ok(bits | DEVCG_ACC_MKNOD),
+        c => Err(io::Error::other(format!("unknown device access {:?}", c))),
+    })
+}
+
+fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> BpfInsn {
+    BpfInsn {
+        code,
+        regs: src << 4 | dst,
+        off,
+        imm,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const READ: i32 = DEVCG_ACC_READ;
+    const WRITE: i32 = DEVCG_ACC_WRITE;
+    const MKNOD: i32 = DEVCG_ACC_MKNOD;
+    const CHAR: i32 = DEVCG_DEV_CHAR;
+    const BLOCK: i32 = DEVCG_DEV_BLOCK;
+
+    fn rule(allow: bool, dev_type: &str, major: Option<i64>, minor: Option<i64>, access: &str) -> DeviceRule {
+        DeviceRule {
+            allow,
+            dev_type: Some(dev_type.to_string()),
+            major,
+            minor,
+            access: Some(access.to_string()),
+        }
+    }
+
+    fn fields(insns: &[BpfInsn]) -> Vec<(u8, u8, i16, i32)> {
+        insns.iter().map(|insn| (insn.code, insn.regs, insn.off, insn.imm)).collect()
+    }
+
+    // interprets the instructions device_filter emits for one access, a jump out of the program panics
+    fn verdict(insns: &[BpfInsn], dev_type: i32, access: i32, major: u32, minor: u32) -> bool {
+        let ctx = [(access << 16 | dev_type) as u32, major, minor];
+        let mut regs = [0u32; 11];
+        let mut pc = 0;
+        loop {
+            let insn = insns[pc];
+            let (dst, src) = ((insn.regs & 0x0f) as usize, (insn.regs >> 4) as usize);
+            let jump = pc + 1 + insn.off as usize;
+            pc += 1;
+            match insn.code {
+                LDX_MEM_W => regs[dst] = ctx[insn.off as usize / 4],
+                ALU32_AND_K => regs[dst] &= insn.imm as u32,
+                ALU32_RSH_K => regs[dst] >>= insn.imm,
+                ALU32_MOV_X => regs[dst] = regs[src],
+                ALU64_MOV_K => regs[dst] = insn.imm as u32,
+                JMP32_JEQ_K if regs[dst] == insn.imm as u32 => pc = jump,
+                JMP32_JNE_K if regs[dst] != insn.imm as u32 => pc = jump,
+                JMP32_JNE_X if regs[dst] != regs[src] => pc = jump,
+                JMP32_JEQ_K | JMP32_JNE_K | JMP32_JNE_X => (),
+                JMP_EXIT => return regs[0] == 1,
+                code => panic!("unexpected opcode {:#x}", code),
+            }
+        }
+    }
+
+    #[test]
+    fn instructions_of_allow_and_deny_rules() {
+        let insns = device_filter(&[rule(false, "a", None, None, "rwm"), rule(true, "c", Some(1), Some(3), "r")]).unwrap();
+
+        let access = R_ACCESS << 4 | 1;
+        assert_eq!(fields(&insns[6..]), vec![
+            // the last rule comes first, allow c 1:3 r
+            (JMP32_JNE_K, R_TYPE, 7, CHAR),
+            (ALU32_MOV_X, access, 0, 0),
+            (ALU32_AND_K, 1, 0, READ),
+            (JMP32_JNE_X, access, 4, 0),
+            (JMP32_JNE_K, R_MAJOR, 3, 1),
+            (JMP32_JNE_K, R_MINOR, 2, 3),
+            (ALU64_MOV_K, 0, 0, 1),
+            (JMP_EXIT, 0, 0, 0),
+            // deny a *:* rwm
+            (ALU32_MOV_X, access, 0, 0),
+            (ALU32_AND_K, 1, 0, DEVCG_ACC_ALL),
+            (JMP32_JEQ_K, 1, 2, 0),
+            (ALU64_MOV_K, 0, 0, 0),
+            (JMP_EXIT, 0, 0, 0),
+            // no rule matched
+            (ALU64_MOV_K, 0, 0, 0),
+            (JMP_EXIT, 0, 0, 0),
+        ]);
+    }
+
+    #[test]
+    fn allow_rule_matches_only_its_device() {
+        let insns = device_filter(&[rule(false, "a", None, None, "rwm"), rule(true, "c", Some(1), Some(3), "rwm")]).unwrap();
+
+        assert!(verdict(&insns, CHAR, READ | WRITE, 1, 3));
+        assert!(!verdict(&insns, CHAR, READ, 1, 5));
+        assert!(!verdict(&insns, CHAR, READ, 2, 3));
+        assert!(!verdict(&insns, BLOCK, READ, 1, 3));
+        assert!(!verdict(&device_filter(&[]).unwrap(), CHAR, READ, 1, 3));
+    }
+
+    #[test]
+    fn later_deny_rule_overrides_allow() {
+        let insns = device_filter(&[rule(true, "a", None, None, "rwm"), rule(false, "c", Some(5), Some(1), "w")]).unwrap();
+
+        assert!(verdict(&insns, CHAR, READ, 5, 1));
+        assert!(!verdict(&insns, CHAR, WRITE, 5, 1));
+        // denying part of the request denies all of it
+        assert!(!verdict(&insns, CHAR, READ | WRITE, 5, 1));
+        assert!(verdict(&insns, CHAR, WRITE, 5, 2));
+        assert!(verdict(&insns, BLOCK, WRITE, 5, 1));
+    }
+
+    #[test]
+    fn wildcard_major_and_minor() {
+        let insns = device_filter(&[
+            rule(false, "a", None, None, "rwm"),
+            rule(true, "c", None, None, "m"),
+            rule(true, "b", Some(-1), Some(-1), "m"),
+            rule(true, "c", Some(136), Some(-1), "rw"),
+        ])
+        .unwrap();
+
+        assert!(verdict(&insns, CHAR, MKNOD, 200, 1));
+        assert!(verdict(&insns, BLOCK, MKNOD, 8, 0));
+        assert!(verdict(&insns, CHAR, READ | WRITE, 136, 7));
+        assert!(!verdict(&insns, CHAR, READ, 137, 7));
+        assert!(!verdict(&insns, BLOCK, READ, 8, 0));
+    }
+
+    #[test]
+    fn allow_rule_covers_only_its_access() {
+        let insns = device_filter(&[rule(false, "a", None, None, "rwm"), rule(true, "c", Some(1), Some(3), "r")]).unwrap();
+
+        assert!(verdict(&insns, CHAR, READ, 1, 3));
+        assert!(!verdict(&insns, CHAR, WRITE, 1, 3));
+        assert!(!verdict(&insns, CHAR, MKNOD, 1, 3));
+        assert!(!verdict(&insns, CHAR, READ | WRITE | MKNOD, 1, 3));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::syscall::{self, CapSets};
+use nix::errno::Errno;
+use std::io;
+
+// the capabilities by number, see include/uapi/linux/capability.h
+pub const CAPABILITIES: [&str; 41] = [
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+// what docker grants, enough for a typical service to drop privileges, bind low ports and manage its files
+const DEFAULT_CAPABILITIES: [&str; 14] = [
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_FSETID",
+    "CAP_FOWNER",
+    "CAP_MKNOD",
+    "CAP_NET_RAW",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETFCAP",
+    "CAP_SETPCAP",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_SYS_CHROOT",
+    "CAP_KILL",
+    "CAP_AUDIT_WRITE",
+];
+
+pub fn default_capabilities() -> Vec<String> {
+    DEFAULT_CAPABILITIES.iter().map(|cap| cap.to_string()).collect()
+}
+
+pub fn is_known(name: &str) -> bool {
+    CAPABILITIES.contains(&name)
+}
+
+// shrinks the bounding set to the capabilities to keep, so setuid binaries and file capabilities cannot exceed them
+// needs CAP_SETPCAP, so it has to happen before switching to the process user
+pub fn drop_bounding(keep: &[String]) -> io::Result<()> {
+    let keep = mask(keep);
+    for cap in 0..64 {
+        match syscall::capbset_read(cap) {
+            Ok(true) if keep & 1 << cap == 0 => syscall::capbset_drop(cap)?,
+            Ok(_) => (),
+            // past the last capability of the running kernel
+            Err(Errno::EINVAL) => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(())
+}
+
+// shrinks the sets the process runs with, switching to a user other than root has already emptied them
+// root regains the bounding set on exec anyway, but the inheritable set would otherwise survive it
+pub fn limit(keep: &[String]) -> io::Result<()> {
+    let keep = mask(keep);
+    let current = syscall::capget()?;
+    syscall::capset(&CapSets {
+        effective: current.effective & keep,
+        permitted: current.permitted & keep,
+        inheritable: current.inheritable & keep,
+    })?;
+
+    Ok(())
+}
+
+fn mask(names: &[String]) -> u64 {
+    names
+        .iter()
+        .filter_map(|name| CAPABILITIES.iter().position(|cap| cap == name))
+        .fold(0, |mask, cap| mask | 1 << cap)
+}
@@ -0,0 +1,169 @@
+use crate::bpf;
+use crate::devices;
+use crate::spec::ContainerSpec;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::statfs::{statfs, CGROUP2_SUPER_MAGIC};
+use nix::unistd::Pid;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{fs, io};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CGROUP_PARENT: &str = "frogc";
+const CONTROLLERS: [&str; 4] = ["cpu", "memory", "pids", "io"];
+const CPU_PERIOD: u64 = 100_000;
+const FREEZE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// a cgroup v2 directory that holds all processes of a single container
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    // only the unified hierarchy is supported, on cgroup v1 hosts containers run without a cgroup
+    pub fn is_supported() -> bool {
+        statfs(CGROUP_ROOT)
+            .map(|s| s.filesystem_type() == CGROUP2_SUPER_MAGIC)
+            .unwrap_or(false)
+    }
+
+    pub fn new(container_id: &str) -> Self {
+        Self {
+            path: Path::new(CGROUP_ROOT).join(CGROUP_PARENT).join(container_id),
+        }
+    }
+
+    pub fn create(&self) -> io::Result<()> {
+        // controllers have to be enabled in every ancestor's subtree_control for the container cgroup to get them
+        let parent = Path::new(CGROUP_ROOT).join(CGROUP_PARENT);
+        fs::create_dir_all(&parent)?;
+        enable_controllers(Path::new(CGROUP_ROOT));
+        enable_controllers(&parent);
+
+        match fs::create_dir(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+            r => r,
+        }
+    }
+
+    pub fn apply_resources(&self, spec: &ContainerSpec) -> io::Result<()> {
+        if let Some(memory) = spec.memory {
+            self.write("memory.max", &memory.to_string())?;
+        }
+
+        if let Some(quota) = spec.cpu_quota {
+            let quota = if quota < 0 { "max".to_string() } else { quota.to_string() };
+            self.write("cpu.max", &format!("{} {}", quota, spec.cpu_period.unwrap_or(CPU_PERIOD)))?;
+        } else if let Some(cpu) = spec.cpu {
+            self.write("cpu.max", &format!("{} {}", cpu as u64 * CPU_PERIOD, CPU_PERIOD))?;
+        }
+
+        if let Some(pids) = spec.pids {
+            let pids = if pids < 0 { "max".to_string() } else { pids.to_string() };
+            self.write("pids.max", &pids)?;
+        }
+
+        // cgroup v2 has no devices controller, device access is checked by a BPF program attached to the cgroup
+        let dir = File::open(&self.path)?;
+        bpf::attach_device_filter(dir.as_fd(), &devices::cgroup_rules(spec))
+            .map_err(|e| io::Error::other(format!("attaching device filter: {}", e)))?;
+
+        Ok(())
+    }
+
+    pub fn add_process(&self, pid: Pid) -> io::Result<()> {
+        self.write("cgroup.procs", &pid.to_string())
+    }
+
+    pub fn processes(&self) -> io::Result<Vec<Pid>> {
+        Ok(self
+            .read("cgroup.procs")?
+            .lines()
+            .filter_map(|line| line.trim().parse().ok())
+            .map(Pid::from_raw)
+            .collect())
+    }
+
+    pub fn remove(&self) -> io::Result<()> {
+        match fs::remove_dir(&self.path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            r => r,
+        }
+    }
+
+    pub fn freeze(&self) -> io::Result<()> {
+        self.write("cgroup.freeze", "1")?;
+        self.wait_frozen(true)
+    }
+
+    pub fn thaw(&self) -> io::Result<()> {
+        self.write("cgroup.freeze", "0")?;
+        self.wait_frozen(false)
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    pub fn open(&self, file: &str) -> io::Result<File> {
+        File::open(self.path.join(file))
+    }
+
+    pub fn read(&self, file: &str) -> io::Result<String> {
+        fs::read_to_string(self.path.join(file))
+    }
+
+    pub fn write(&self, file: &str, value: &str) -> io::Result<()> {
+        fs::write(self.path.join(file), value)
+    }
+
+    // writing cgroup.freeze only requests the state change, the kernel reports completion through cgroup.events
+    // cgroup.events raises a priority event on every change, so we can poll on it instead of busy looping
+    fn wait_frozen(&self, frozen: bool) -> io::Result<()> {
+        let expected = if frozen { "1" } else { "0" };
+        let mut events = self.open("cgroup.events")?;
+        let deadline = Instant::now() + FREEZE_TIMEOUT;
+
+        loop {
+            let mut content = String::new();
+            events.seek(SeekFrom::Start(0))?;
+            events.read_to_string(&mut content)?;
+
+            if parse_flat_keyed(&content, "frozen").as_deref() == Some(expected) {
+                return Ok(());
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("cgroup did not report frozen {}", expected),
+                ));
+            }
+
+            let timeout = PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX);
+            let mut fds = [PollFd::new(events.as_fd(), PollFlags::POLLPRI)];
+            poll(&mut fds, timeout)?;
+        }
+    }
+}
+
+// reads a value from a flat keyed cgroup file like cgroup.events or memory.events ("key value" per line)
+pub fn parse_flat_keyed(content: &str, key: &str) -> Option<String> {
+    content.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        (k == key).then(|| v.trim().to_string())
+    })
+}
+
+fn enable_controllers(path: &Path) {
+    // enabling a controller that is not available fails, so we enable them one by one and ignore errors
+    for controller in CONTROLLERS {
+        if let Err(e) = fs::write(path.join("cgroup.subtree_control"), format!("+{}", controller)) {
+            log::debug!("could not enable {} controller in {}: {}", controller, path.display(), e);
+        }
+    }
+}
@@ -1,12 +1,28 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(name = "frogc")]
 #[command(about = "A mutable, CLI-only container runtime", long_about = None)]
 pub struct FrogCli {
-    #[arg(long)]
+    // directory holding the state of all containers, runc calls it --root
+    #[arg(long, alias = "root", global = true)]
     pub run_dir: Option<String>,
 
+    // write frogc's own messages to this file instead of stderr
+    #[arg(long, global = true)]
+    pub log: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    pub log_format: LogFormat,
+
+    // log debug messages, RUST_LOG can be used for finer control
+    #[arg(long, global = true)]
+    pub debug: bool,
+
+    // accepted for compatibility with runc, frogc always manages cgroups through the cgroupfs
+    #[arg(long, global = true)]
+    pub systemd_cgroup: bool,
+
     #[command(subcommand)]
     pub(crate) command: Commands,
 }
@@ -15,14 +31,205 @@ pub struct FrogCli {
 pub enum Commands {
     // Create a new container
     Create {
+        #[arg(long, required_unless_present = "bundle", conflicts_with = "bundle")]
+        spec: Option<String>,
+
+        // directory with an OCI runtime spec config.json, used instead of --spec
         #[arg(long)]
-        spec: String,
+        bundle: Option<String>,
+
+        // unix socket that receives the pty master when the spec enables a terminal
+        #[arg(long)]
+        console_socket: Option<String>,
+
+        // write the pid of the container process to this file, only for containers created from a bundle
+        #[arg(long, requires = "bundle")]
+        pid_file: Option<String>,
+
+        // capture the container's stdout and stderr into this file, relative paths are inside the container run dir
+        #[arg(long)]
+        log_path: Option<String>,
 
         container_id: String,
     },
+    // Start a created container in the background
     Start {
+        // stay in the foreground until the container exits
+        #[arg(long)]
+        foreground: bool,
+
+        container_id: String,
+    },
+    // Connect to the stdio of a container started in the background
+    Attach {
+        // only print the container's output, without forwarding stdin
+        #[arg(long)]
+        read_only: bool,
+
+        // key sequence that detaches from the container without stopping it
+        #[arg(long, default_value = "ctrl-p,ctrl-q")]
+        detach_keys: String,
+
+        container_id: String,
+    },
+    // Send a signal to the container's process
+    Kill {
+        // signal every process in the container's cgroup
+        #[arg(short, long)]
+        all: bool,
+
+        container_id: String,
+
+        // signal name (with or without the SIG prefix) or number
+        #[arg(default_value = "SIGTERM")]
+        signal: String,
+    },
+    // Set the terminal size of a container whose pty is held by its shim
+    Resize {
+        #[arg(long)]
+        rows: u16,
+
+        #[arg(long)]
+        cols: u16,
+
+        container_id: String,
+    },
+    // Print the state of a container as JSON
+    State {
+        container_id: String,
+    },
+    // List all containers
+    List {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    // Print the captured output of a container
+    Logs {
+        // keep printing new output until the container stops
+        #[arg(short, long)]
+        follow: bool,
+
+        // only print the last N lines
+        #[arg(long)]
+        tail: Option<usize>,
+
+        container_id: String,
+    },
+    // Create and start a container, staying attached until it exits
+    Run {
+        #[arg(long)]
+        spec: String,
+
+        // keep stdin open and forward it to the container
+        #[arg(short, long)]
+        interactive: bool,
+
+        // allocate a pseudo-terminal for the container
+        #[arg(short, long)]
+        tty: bool,
+
+        container_id: String,
+    },
+    // Freeze all processes of a running container
+    Pause {
+        container_id: String,
+    },
+    // Thaw a paused container
+    Resume {
         container_id: String,
     },
+    // Print a snapshot of the container's resource usage as JSON
+    Stats {
+        container_id: String,
+    },
+    // Stream resource usage, OOM and exit events as JSON lines
+    Events {
+        // seconds between two stats events
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+
+        container_id: String,
+    },
+    // List the processes running inside a container
+    Ps {
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+
+        container_id: String,
+    },
+    // Remove a stopped container
+    Delete {
+        // kill the container first if it is still running
+        #[arg(short, long)]
+        force: bool,
+
+        container_id: String,
+    },
+    // Run an additional process inside a running container
+    Exec {
+        // JSON file with an OCI process, used instead of the command and the other process options
+        #[arg(long, conflicts_with_all = ["command", "tty", "cwd", "env"])]
+        process: Option<String>,
+
+        // return once the process has been started instead of waiting for it
+        #[arg(short, long)]
+        detach: bool,
+
+        // write the pid of the process to this file
+        #[arg(long)]
+        pid_file: Option<String>,
+
+        // unix socket that receives the pty master when a terminal is requested
+        #[arg(long)]
+        console_socket: Option<String>,
+
+        // allocate a pseudo-terminal for the process
+        #[arg(short, long)]
+        tty: bool,
+
+        // working directory of the process, defaults to the container's
+        #[arg(long)]
+        cwd: Option<String>,
+
+        // additional environment variable, NAME=VALUE
+        #[arg(short, long)]
+        env: Vec<String>,
+
+        container_id: String,
+
+        #[arg(trailing_var_arg = true, required_unless_present = "process")]
+        command: Vec<String>,
+    },
+    // Print a default spec to start from
+    Spec {
+        // write the spec to this file instead of stdout, an existing file is not overwritten
+        #[arg(short, long)]
+        output: Option<String>,
+
+        // absolute path of the container's root filesystem, ./rootfs by default
+        #[arg(long)]
+        rootfs: Option<String>,
+
+        // map root in the container to the current user, for running without privileges
+        #[arg(long)]
+        rootless: bool,
+    },
+    // Check a spec without creating a container
+    Validate {
+        #[arg(long, required_unless_present = "bundle", conflicts_with = "bundle")]
+        spec: Option<String>,
+
+        // directory with an OCI runtime spec config.json, used instead of --spec
+        #[arg(long)]
+        bundle: Option<String>,
+    },
+    // Print the features frogc supports as an OCI features document
+    Features,
+    // Work with images in OCI image layout directories
+    Image {
+        #[command(subcommand)]
+        command: ImageCommands,
+    },
     #[command(hide = true)]
     ReExec {
         #[command(subcommand)]
@@ -30,9 +237,41 @@ pub enum Commands {
     }
 }
 
+#[derive(Subcommand)]
+pub enum ImageCommands {
+    // Unpack the layers of an image into a directory
+    Unpack {
+        // the layout directory, followed by :tag if it holds more than one image
+        image: String,
+
+        // the directory to unpack into, it must not exist or be empty
+        dest: String,
+
+        // also write a spec running the image's command in dest to this file, - for stdout
+        #[arg(long)]
+        spec: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum ReExecCommands {
     Start {
+        // pipe the container reports the outcome of its setup to before waiting to be started
+        #[arg(long)]
+        sync_fd: Option<i32>,
+
         container_id: String,
     }
 }
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
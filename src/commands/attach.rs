@@ -0,0 +1,236 @@
+use crate::console;
+use crate::console::RawTerminal;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::shim;
+use crate::shim::Request;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::unistd::isatty;
+use std::io;
+use std::io::{Read, Write};
+use std::os::fd::AsFd;
+use std::os::unix::net::UnixStream;
+
+// connects our stdio to a container started in the background
+// returns the exit code of the container if it exited while we were attached, 0 after detaching
+pub fn run(
+    context: FrogContext,
+    container_id: String,
+    read_only: bool,
+    detach_keys: String,
+) -> Result<i32, ContainerError> {
+    let detach_keys = parse_detach_keys(&detach_keys)?;
+
+    let state = {
+        let _lock = context.lookup_container(&container_id)?;
+
+        context
+            .read_state(&container_id)
+            .map_err(ContainerError::wrap)?
+    };
+    if state.status == "stopped" {
+        return Err(ContainerError::InvalidState(
+            "Container is not running".to_string(),
+        ));
+    }
+
+    let (response, stream) = shim::connect(&context, &container_id, &Request::Attach { read_only })
+        .map_err(ContainerError::wrap)?
+        .ok_or(ContainerError::InvalidState(
+            "Container is not running under a shim".to_string(),
+        ))?;
+    response.into_result()?;
+
+    let attachment = Attachment {
+        context: &context,
+        container_id: &container_id,
+        terminal: state.spec.terminal.unwrap_or(false),
+        read_only,
+        detach_keys: &detach_keys,
+    };
+    let detached = attachment
+        .proxy(stream)
+        .map_err(WrapError::wrapper("proxying container stdio"))
+        .map_err(ContainerError::wrap)?;
+    if detached {
+        return Ok(0);
+    }
+
+    // the shim only closes the connection after it recorded the exit status
+    let _lock = context.lookup_container(&container_id)?;
+    let state = context
+        .read_state(&container_id)
+        .map_err(ContainerError::wrap)?;
+
+    Ok(state.exit_code.unwrap_or(0))
+}
+
+struct Attachment<'a> {
+    context: &'a FrogContext,
+    container_id: &'a str,
+    terminal: bool,
+    read_only: bool,
+    detach_keys: &'a [u8],
+}
+
+impl Attachment<'_> {
+    // copies between our stdio and the shim until the container exits or the detach keys are pressed
+    // returns whether we detached
+    fn proxy(&self, mut stream: UnixStream) -> io::Result<bool> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+        let mut stderr = io::stderr();
+
+        // a read only attacher never owns the terminal, so its size is left to whoever does
+        let owns_terminal = self.terminal && !self.read_only && isatty(stdin.as_fd()).unwrap_or(false);
+        let _raw_terminal = if owns_terminal {
+            Some(RawTerminal::enable()?)
+        } else {
+            None
+        };
+
+        let sigwinch = if owns_terminal {
+            self.resize();
+
+            let mut mask = SigSet::empty();
+            mask.add(Signal::SIGWINCH);
+            mask.thread_block()?;
+            Some(SignalFd::with_flags(&mask, SfdFlags::SFD_CLOEXEC)?)
+        } else {
+            None
+        };
+
+        let mut stdin_open = !self.read_only;
+        let mut matched = 0;
+        let mut pending = vec![];
+        let mut buf = [0u8; 4096];
+
+        loop {
+            let mut fds = vec![PollFd::new(stream.as_fd(), PollFlags::POLLIN)];
+            if let Some(sigwinch) = &sigwinch {
+                fds.push(PollFd::new(sigwinch.as_fd(), PollFlags::POLLIN));
+            }
+            if stdin_open {
+                fds.push(PollFd::new(stdin.as_fd(), PollFlags::POLLIN));
+            }
+
+            match poll(&mut fds, PollTimeout::NONE) {
+                Err(Errno::EINTR) => continue,
+                r => r?,
+            };
+
+            let output_ready = fds[0].any().unwrap_or(false);
+            let resized = sigwinch.is_some() && fds[1].any().unwrap_or(false);
+            let stdin_ready = stdin_open && fds[fds.len() - 1].any().unwrap_or(false);
+
+            if output_ready {
+                let n = stream.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(false);
+                }
+
+                pending.extend_from_slice(&buf[..n]);
+                for (stream_id, data) in shim::read_frames(&mut pending) {
+                    if stream_id == shim::STDERR {
+                        stderr.write_all(&data)?;
+                    } else {
+                        stdout.write_all(&data)?;
+                        stdout.flush()?;
+                    }
+                }
+            }
+
+            if resized && let Some(sigwinch) = &sigwinch {
+                sigwinch.read_signal()?;
+                self.resize();
+            }
+
+            if stdin_ready {
+                // the shim takes a closed connection for a detach, so on EOF we only stop reading
+                let n = stdin.lock().read(&mut buf)?;
+                if n == 0 {
+                    stdin_open = false;
+                    continue;
+                }
+
+                let (input, detached) = self.scan_detach_keys(&buf[..n], &mut matched);
+                stream.write_all(&input)?;
+                if detached {
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    // the keys are held back while they could still be the start of the detach sequence
+    // returns the input to forward and whether the whole sequence was typed
+    fn scan_detach_keys(&self, data: &[u8], matched: &mut usize) -> (Vec<u8>, bool) {
+        let keys = self.detach_keys;
+        let mut input = vec![];
+
+        for &b in data {
+            if b != keys[*matched] {
+                input.extend_from_slice(&keys[..*matched]);
+                *matched = 0;
+            }
+
+            if b == keys[*matched] {
+                *matched += 1;
+                if *matched == keys.len() {
+                    return (input, true);
+                }
+            } else {
+                input.push(b);
+            }
+        }
+
+        (input, false)
+    }
+
+    fn resize(&self) {
+        let Ok(size) = console::window_size(io::stdin().as_fd()) else {
+            return;
+        };
+
+        let request = Request::Resize {
+            rows: size.ws_row,
+            cols: size.ws_col,
+        };
+        let result = shim::request(self.context, self.container_id, &request)
+            .and_then(|response| match response.and_then(|r| r.error) {
+                Some(e) => Err(io::Error::other(e)),
+                None => Ok(()),
+            });
+        if let Err(e) = result {
+            log::warn!("could not resize container terminal: {}", e);
+        }
+    }
+}
+
+// parses a sequence like "ctrl-p,ctrl-q", every key is either ctrl-<key> or a single character
+fn parse_detach_keys(keys: &str) -> Result<Vec<u8>, ContainerError> {
+    let invalid = || ContainerError::wrap(io::Error::other(format!("invalid detach keys {}", keys)));
+
+    keys.split(',')
+        .map(|key| {
+            let control = match key.strip_prefix("ctrl-") {
+                Some(control) => control.as_bytes(),
+                None => {
+                    return match key.as_bytes() {
+                        [b] if b.is_ascii() => Ok(*b),
+                        _ => Err(invalid()),
+                    };
+                }
+            };
+
+            match control {
+                [b @ b'a'..=b'z'] => Ok(b - b'a' + 1),
+                [b @ (b'@' | b'[' | b'\\' | b']' | b'^' | b'_')] => Ok(b & 0x1f),
+                _ => Err(invalid()),
+            }
+        })
+        .collect()
+}
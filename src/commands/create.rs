@@ -1,38 +1,185 @@
+use crate::commands::{delete, start};
 use crate::context::FrogContext;
-use crate::errors::ContainerError;
+use crate::errors::{ContainerError, WrapError};
+use crate::namespaces;
+use crate::oci;
+use crate::reexec::Stdio;
+use crate::rootfs;
 use crate::spec;
+use crate::spec::LinuxIdMapping;
+use crate::validate;
+use chrono::Utc;
+use nix::fcntl::OFlag;
+use nix::sys::stat::Mode;
+use nix::sys::wait::waitpid;
+use nix::unistd::{chown, mkfifo, pipe2, Gid, Pid, Uid};
+use std::fs::File;
 use std::io::Read;
 use std::{fs, io};
 
 pub fn run(
     context: FrogContext,
-    spec_path: String,
+    spec_path: Option<String>,
+    bundle: Option<String>,
     container_id: String,
+    console_socket: Option<String>,
+    pid_file: Option<String>,
+    log_path: Option<String>,
 ) -> Result<(), ContainerError> {
-    // read the spec
+    let Some(bundle) = bundle else {
+        // clap makes sure one of them is given
+        let mut spec = read_spec(spec_path.as_deref().unwrap_or("-"))?;
+        if let Some(log_path) = log_path {
+            spec.log.get_or_insert_default().path = Some(log_path);
+        }
+
+        return create(&context, &container_id, spec, console_socket, None);
+    };
+
+    let bundle = fs::canonicalize(bundle)
+        .map_err(WrapError::wrapper("resolving bundle path"))
+        .map_err(ContainerError::wrap)?;
+    let spec = oci::load_bundle(&bundle)?;
+
+    // nobody would be there to receive the pty master
+    if spec.terminal.unwrap_or(false) && console_socket.is_none() {
+        return Err(ContainerError::InvalidState(
+            "--console-socket is required when process.terminal is true".to_string(),
+        ));
+    }
+
+    let bundle = bundle.to_string_lossy().to_string();
+    create(&context, &container_id, spec, console_socket, Some(bundle))?;
+
+    // like runc, a container that could not be set up does not stay around
+    let pid = match create_process(&context, &container_id) {
+        Ok(pid) => pid,
+        Err(e) => {
+            if let Err(e) = delete::destroy(&context, &container_id) {
+                log::warn!("cleaning up container {}: {}", container_id, e);
+            }
+            return Err(e);
+        }
+    };
+
+    if let Some(pid_file) = pid_file {
+        fs::write(pid_file, pid.to_string())
+            .map_err(WrapError::wrapper("writing pid file"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    Ok(())
+}
+
+// sets up the process of a container created from a bundle, it then waits on the exec fifo until start
+// the process reports over the sync pipe whether its setup succeeded
+fn create_process(context: &FrogContext, container_id: &str) -> Result<Pid, ContainerError> {
+    let state = context
+        .read_state(container_id)
+        .map_err(ContainerError::wrap)?;
+
+    // the process opens the fifo as the container's root user, which is not the host's root with a user namespace
+    let fifo_path = start::exec_fifo_path(context, container_id);
+    mkfifo(&fifo_path, Mode::from_bits_truncate(0o622))
+        .map_err(WrapError::wrapper("creating exec fifo"))
+        .map_err(ContainerError::wrap)?;
+    if namespaces::is_private(&state.spec, "user") {
+        let root = |mappings: &Option<Vec<LinuxIdMapping>>| {
+            mappings
+                .iter()
+                .flatten()
+                .find(|m| m.container_id == 0)
+                .map(|m| m.host_id)
+        };
+        chown(
+            &fifo_path,
+            root(&state.spec.uid_mappings).map(Uid::from_raw),
+            root(&state.spec.gid_mappings).map(Gid::from_raw),
+        )
+        .map_err(WrapError::wrapper("changing owner of exec fifo"))
+        .map_err(ContainerError::wrap)?;
+    }
+
+    let (sync_read, sync_write) = pipe2(OFlag::O_CLOEXEC).map_err(ContainerError::wrap)?;
+    let stdio = Stdio {
+        sync: Some(sync_write),
+        ..Default::default()
+    };
+    let pid = start::spawn(context, container_id, stdio)?;
+
+    let mut report = String::new();
+    File::from(sync_read)
+        .read_to_string(&mut report)
+        .map_err(ContainerError::wrap)?;
+
+    // the process exits after reporting a failure, it has to be gone before its cgroup can be removed
+    if report != "ok" {
+        let _ = waitpid(pid, None);
+    }
+
+    match report.as_str() {
+        "ok" => Ok(pid),
+        "" => Err(ContainerError::wrap(io::Error::other(
+            "container process exited during setup",
+        ))),
+        e => Err(ContainerError::wrap(io::Error::other(e.to_string()))),
+    }
+}
+
+// reads a spec from a file, "-" reads it from stdin
+pub fn read_spec(spec_path: &str) -> Result<spec::ContainerSpec, ContainerError> {
     let spec_content = if spec_path == "-" {
         let mut buffer = String::new();
         io::stdin().read_to_string(&mut buffer).map_err(ContainerError::wrap)?;
         buffer
     } else {
-        fs::read_to_string(&spec_path).map_err(ContainerError::wrap)?
+        fs::read_to_string(spec_path).map_err(ContainerError::wrap)?
     };
 
-    let spec: spec::ContainerSpec = serde_json::from_str(&spec_content).map_err(ContainerError::wrap)?;
+    serde_json::from_str(&spec_content).map_err(ContainerError::wrap)
+}
 
-    let (exists, _lock) = context.lock_container(&container_id).map_err(ContainerError::wrap)?;
+pub fn create(
+    context: &FrogContext,
+    container_id: &str,
+    spec: spec::ContainerSpec,
+    console_socket: Option<String>,
+    bundle: Option<String>,
+) -> Result<(), ContainerError> {
+    // checked before locking, which would already create the run dir
+    validate::validate(&spec)?;
+
+    let (exists, _lock) = context.lock_container(container_id).map_err(ContainerError::wrap)?;
     if exists {
         return Err(ContainerError::AlreadyExists);
     }
 
+    // nothing refers to the run dir yet, so it is removed again if the rootfs cannot be assembled
+    let rootfs_mount = match rootfs::mount_rootfs(context, container_id, &spec) {
+        Ok(path) => path.map(|path| path.to_string_lossy().to_string()),
+        Err(e) => {
+            let _ = fs::remove_dir_all(context.container_run_dir(container_id));
+            return Err(ContainerError::wrap(WrapError::wrapper("mounting rootfs")(e)));
+        }
+    };
+
     let state = spec::ContainerState {
-        id: container_id.clone(),
+        id: container_id.to_string(),
         spec,
         status: "stopped".to_string(),
         pid: None,
+        pid_start_time: None,
+        console_socket,
+        rootfs_mount,
+        bundle,
+        created_at: Some(Utc::now()),
+        exit_code: None,
+        exit_signal: None,
+        oom_killed: None,
+        finished_at: None,
     };
 
-    context.write_state(&container_id, state).map_err(ContainerError::wrap)?;
+    context.write_state(container_id, state).map_err(ContainerError::wrap)?;
 
     Ok(())
 }
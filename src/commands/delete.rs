@@ -0,0 +1,93 @@
+use crate::cgroup::Cgroup;
+use crate::commands::kill;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::hooks;
+use crate::oci;
+use crate::rootfs;
+use nix::sys::signal::Signal;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// removes a stopped container, with force a running one is killed first
+// like runc, forcing the removal of a container that does not exist succeeds
+pub fn run(context: FrogContext, container_id: String, force: bool) -> Result<(), ContainerError> {
+    let state = {
+        let _lock = match context.lookup_container(&container_id) {
+            Ok(lock) => lock,
+            Err(ContainerError::NotFound) if force => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        context
+            .read_state(&container_id)
+            .map_err(ContainerError::wrap)?
+    };
+
+    match state.status.as_str() {
+        "stopped" => (),
+        // the process of a created container has not run anything of the container yet
+        "created" => stop(&context, &container_id, state.pid)?,
+        _ if force => stop(&context, &container_id, state.pid)?,
+        _ => {
+            return Err(ContainerError::InvalidState(
+                "Container is running, stop it first or use --force".to_string(),
+            ));
+        }
+    }
+
+    destroy(&context, &container_id)?;
+
+    hooks::run(&state.spec, "poststop", &oci::State::new(&state).with_status("stopped"))
+        .map_err(ContainerError::wrap)
+}
+
+// removes the cgroup and run dir of a container whose processes are gone
+pub fn destroy(context: &FrogContext, container_id: &str) -> Result<(), ContainerError> {
+    let _lock = context.lookup_container(container_id)?;
+
+    if Cgroup::is_supported() {
+        Cgroup::new(container_id)
+            .remove()
+            .map_err(WrapError::wrapper("removing cgroup"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    rootfs::unmount_rootfs(context, container_id)
+        .map_err(WrapError::wrapper("unmounting rootfs"))
+        .map_err(ContainerError::wrap)?;
+
+    fs::remove_dir_all(context.container_run_dir(container_id))
+        .map_err(WrapError::wrapper("removing run dir"))
+        .map_err(ContainerError::wrap)
+}
+
+// SIGKILL also ends frozen processes, so this works for paused containers too
+// whoever reaps the container process records that it stopped, we wait for that without holding the lock
+fn stop(context: &FrogContext, container_id: &str, pid: Option<u32>) -> Result<(), ContainerError> {
+    kill::signal_all(container_id, pid, Signal::SIGKILL)?;
+
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    loop {
+        let state = {
+            let _lock = context.lookup_container(container_id)?;
+            context
+                .read_state(container_id)
+                .map_err(ContainerError::wrap)?
+        };
+        if state.status == "stopped" {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(ContainerError::InvalidState(
+                "Container did not stop after SIGKILL".to_string(),
+            ));
+        }
+        thread::sleep(STOP_POLL_INTERVAL);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::cgroup::Cgroup;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::stats;
+use crate::stats::{Event, Stats};
+use crate::syscall::pidfd_open;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::unistd::Pid;
+use serde::Serialize;
+use std::os::fd::AsFd;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize)]
+struct OomEvent {
+    #[serde(rename = "oomKills")]
+    oom_kills: u64,
+}
+
+pub fn run(context: FrogContext, container_id: String, interval: u64) -> Result<(), ContainerError> {
+    let state = {
+        let _lock = context.lookup_container(&container_id)?;
+
+        context
+            .read_state(&container_id)
+            .map_err(ContainerError::wrap)?
+    };
+
+    let pid = match state.pid {
+        Some(pid) if state.status == "running" || state.status == "paused" => pid,
+        _ => {
+            return Err(ContainerError::InvalidState(
+                "Container is not running".to_string(),
+            ))
+        }
+    };
+
+    let cgroup = Cgroup::new(&container_id);
+    let interval = Duration::from_secs(interval.max(1));
+
+    // the pidfd becomes readable when the container exits and memory.events raises a priority event when it changes
+    // polling both lets us report exits and OOM kills right away instead of on the next interval
+    let pidfd = pidfd_open(Pid::from_raw(pid as i32))
+        .map_err(WrapError::wrapper("opening container process"))
+        .map_err(ContainerError::wrap)?;
+    let memory_events = cgroup.open("memory.events").ok();
+
+    let mut oom_kills = stats::oom_kills(&cgroup).map_err(ContainerError::wrap)?;
+    let mut next_stats = Instant::now();
+
+    loop {
+        if Instant::now() >= next_stats {
+            let stats = stats::collect(&cgroup)
+                .map_err(WrapError::wrapper("reading cgroup stats"))
+                .map_err(ContainerError::wrap)?;
+            Event::new("stats", &container_id, Some(stats))
+                .print()
+                .map_err(ContainerError::wrap)?;
+            next_stats += interval;
+        }
+
+        let mut fds = vec![PollFd::new(pidfd.as_fd(), PollFlags::POLLIN)];
+        if let Some(memory_events) = &memory_events {
+            fds.push(PollFd::new(memory_events.as_fd(), PollFlags::POLLPRI));
+        }
+
+        let remaining = next_stats.saturating_duration_since(Instant::now());
+        poll(&mut fds, PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX))
+            .map_err(ContainerError::wrap)?;
+
+        let exited = fds[0].any().unwrap_or(false);
+
+        // the cgroup may already be gone once the container exited
+        let current_oom_kills = stats::oom_kills(&cgroup).unwrap_or(oom_kills);
+        if current_oom_kills > oom_kills {
+            oom_kills = current_oom_kills;
+            Event::new("oom", &container_id, Some(OomEvent { oom_kills }))
+                .print()
+                .map_err(ContainerError::wrap)?;
+        }
+
+        if exited {
+            Event::<Stats>::new("exit", &container_id, None)
+                .print()
+                .map_err(ContainerError::wrap)?;
+            return Ok(());
+        }
+    }
+}
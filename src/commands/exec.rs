@@ -0,0 +1,273 @@
+use crate::cgroup::Cgroup;
+use crate::commands::{reexec_start, start};
+use crate::console;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::oci;
+use crate::spec;
+use crate::spec::ContainerState;
+use nix::fcntl::OFlag;
+use nix::sched::{setns, CloneFlags};
+use nix::sys::wait::waitpid;
+use nix::unistd::{chdir, fork, getpid, pipe2, setresgid, setresuid, ForkResult, Gid, Uid};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::OwnedFd;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::net::UnixStream;
+use std::{fs, io, process};
+
+// the user namespace goes first, it owns the others and joining it grants the capabilities needed for them
+// the mount namespace goes last, /proc is the container's once we joined it
+const NAMESPACES: [(&str, CloneFlags); 7] = [
+    ("user", CloneFlags::CLONE_NEWUSER),
+    ("ipc", CloneFlags::CLONE_NEWIPC),
+    ("uts", CloneFlags::CLONE_NEWUTS),
+    ("net", CloneFlags::CLONE_NEWNET),
+    ("pid", CloneFlags::CLONE_NEWPID),
+    ("cgroup", CloneFlags::CLONE_NEWCGROUP),
+    ("mnt", CloneFlags::CLONE_NEWNS),
+];
+
+// a process to run inside a running container
+pub struct Exec {
+    pub container_id: String,
+    // OCI process JSON, used instead of the command line
+    pub process: Option<String>,
+    pub command: Vec<String>,
+    pub tty: bool,
+    pub cwd: Option<String>,
+    pub env: Vec<String>,
+    pub detach: bool,
+    pub pid_file: Option<String>,
+    pub console_socket: Option<String>,
+}
+
+struct Process {
+    args: Vec<String>,
+    env: Option<Vec<String>>,
+    cwd: String,
+    user: Option<spec::User>,
+    capabilities: Option<Vec<String>>,
+    terminal: bool,
+}
+
+impl Exec {
+    // returns the exit code of the process, or 0 once it has been started with detach
+    pub fn run(self, context: FrogContext) -> Result<i32, ContainerError> {
+        let state = {
+            let _lock = context.lookup_container(&self.container_id)?;
+
+            context
+                .read_state(&self.container_id)
+                .map_err(ContainerError::wrap)?
+        };
+        if state.status != "running" {
+            return Err(ContainerError::InvalidState(
+                "Container is not running".to_string(),
+            ));
+        }
+        let pid = state.pid.ok_or(ContainerError::InvalidState(
+            "Container has no process".to_string(),
+        ))?;
+
+        let process_spec = self.process(&state)?;
+
+        // the pty master has to go somewhere, we do not proxy it ourselves
+        let console_socket = match (&self.console_socket, process_spec.terminal) {
+            (Some(path), true) => Some(
+                UnixStream::connect(path)
+                    .map_err(WrapError::wrapper("connecting to console socket"))
+                    .map_err(ContainerError::wrap)?,
+            ),
+            (None, true) => {
+                return Err(ContainerError::InvalidState(
+                    "--console-socket is required when a terminal is requested".to_string(),
+                ));
+            }
+            (_, false) => None,
+        };
+
+        let (pid_read, pid_write) = pipe2(OFlag::O_CLOEXEC).map_err(ContainerError::wrap)?;
+        let (error_read, error_write) = pipe2(OFlag::O_CLOEXEC).map_err(ContainerError::wrap)?;
+
+        match unsafe { fork() }.map_err(ContainerError::wrap)? {
+            ForkResult::Child => {
+                drop(pid_read);
+                drop(error_read);
+
+                let code = self.enter(pid, &process_spec, console_socket, pid_write, File::from(error_write));
+                process::exit(code);
+            }
+            ForkResult::Parent { child } => {
+                drop(pid_write);
+                drop(error_write);
+
+                // the error pipe is closed without a message once the process has been executed
+                let mut error = String::new();
+                File::from(error_read)
+                    .read_to_string(&mut error)
+                    .map_err(ContainerError::wrap)?;
+                if !error.is_empty() {
+                    let _ = waitpid(child, None);
+                    return Err(ContainerError::wrap(io::Error::other(error)));
+                }
+
+                let mut exec_pid = String::new();
+                File::from(pid_read)
+                    .read_to_string(&mut exec_pid)
+                    .map_err(ContainerError::wrap)?;
+                if let Some(pid_file) = &self.pid_file {
+                    fs::write(pid_file, &exec_pid)
+                        .map_err(WrapError::wrapper("writing pid file"))
+                        .map_err(ContainerError::wrap)?;
+                }
+
+                let status = waitpid(child, None).map_err(ContainerError::wrap)?;
+                Ok(start::exit_code(status))
+            }
+        }
+    }
+
+    fn process(&self, state: &ContainerState) -> Result<Process, ContainerError> {
+        if let Some(path) = &self.process {
+            let content = fs::read_to_string(path)
+                .map_err(WrapError::wrapper("reading process file"))
+                .map_err(ContainerError::wrap)?;
+            let process: oci::Process = serde_json::from_str(&content).map_err(ContainerError::wrap)?;
+            for field in process.unsupported.keys().chain(process.user.unsupported.keys()) {
+                log::warn!("ignoring unsupported process field {}", field);
+            }
+
+            return Ok(Process {
+                args: process.args,
+                env: process.env,
+                cwd: process.cwd,
+                user: Some(spec::User {
+                    uid: process.user.uid,
+                    gid: process.user.gid,
+                    additional_gids: process.user.additional_gids,
+                }),
+                capabilities: process.capabilities.and_then(oci::Capabilities::into_list),
+                terminal: process.terminal.unwrap_or(false),
+            });
+        }
+
+        // the process gets the container's environment, extended by the variables given to exec
+        let mut env = state.spec.env.clone();
+        if !self.env.is_empty() {
+            env.get_or_insert_default().extend(self.env.iter().cloned());
+        }
+
+        Ok(Process {
+            args: self.command.clone(),
+            env,
+            cwd: self
+                .cwd
+                .clone()
+                .or_else(|| state.spec.work_dir.clone())
+                .unwrap_or_else(|| "/".to_string()),
+            user: state.spec.user.clone(),
+            capabilities: state.spec.capabilities.clone(),
+            terminal: self.tty,
+        })
+    }
+
+    // runs in a child of the CLI, joins the container and forks the process, which is needed to really enter the pid namespace
+    // the pid of the process is reported to the CLI, without detach we wait for it and pass on its exit code
+    // errors are reported over the error pipe, which the process closes by executing
+    fn enter(
+        &self,
+        pid: u32,
+        process_spec: &Process,
+        console_socket: Option<UnixStream>,
+        pid_write: OwnedFd,
+        mut error: File,
+    ) -> i32 {
+        if let Err(e) = join_container(&self.container_id, pid) {
+            let _ = write!(error, "joining container: {}", e);
+            return 1;
+        }
+
+        match unsafe { fork() } {
+            Err(e) => {
+                let _ = write!(error, "{}", e);
+                1
+            }
+            Ok(ForkResult::Child) => {
+                drop(pid_write);
+                if let Err(e) = exec(process_spec, console_socket) {
+                    let _ = write!(error, "{}", e);
+                }
+                process::exit(1);
+            }
+            Ok(ForkResult::Parent { child }) => {
+                drop(error);
+                drop(console_socket);
+                let _ = File::from(pid_write).write_all(child.to_string().as_bytes());
+
+                if self.detach {
+                    return 0;
+                }
+
+                match waitpid(child, None) {
+                    Ok(status) => start::exit_code(status),
+                    Err(_) => 1,
+                }
+            }
+        }
+    }
+}
+
+// moves the calling process into the container's cgroup and namespaces
+fn join_container(container_id: &str, pid: u32) -> io::Result<()> {
+    let cgroup = Cgroup::new(container_id);
+    if Cgroup::is_supported() && cgroup.exists() {
+        cgroup.add_process(getpid())?;
+    }
+
+    // namespaces the container shares with us cannot be joined again
+    let mut namespaces = vec![];
+    for (name, flag) in NAMESPACES {
+        let path = format!("/proc/{}/ns/{}", pid, name);
+        let theirs = fs::metadata(&path)?;
+        let ours = fs::metadata(format!("/proc/self/ns/{}", name))?;
+        if theirs.dev() == ours.dev() && theirs.ino() == ours.ino() {
+            continue;
+        }
+
+        namespaces.push((File::open(path)?, flag));
+    }
+
+    for (file, flag) in namespaces {
+        setns(&file, flag)?;
+
+        // like the container process, we act as the namespace's root until we switch to the process user
+        if flag == CloneFlags::CLONE_NEWUSER {
+            setresgid(Gid::from_raw(0), Gid::from_raw(0), Gid::from_raw(0))?;
+            setresuid(Uid::from_raw(0), Uid::from_raw(0), Uid::from_raw(0))?;
+        }
+    }
+
+    Ok(())
+}
+
+// only returns if the process could not be executed
+fn exec(process: &Process, console_socket: Option<UnixStream>) -> Result<(), ContainerError> {
+    if let Some(console_socket) = console_socket {
+        console::setup_exec(&console_socket)
+            .map_err(WrapError::wrapper("setting up console"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    chdir(process.cwd.as_str())
+        .map_err(WrapError::wrapper("changing working directory"))
+        .map_err(ContainerError::wrap)?;
+
+    reexec_start::exec_process(
+        &process.args,
+        process.env.as_deref(),
+        process.user.as_ref(),
+        process.capabilities.as_deref(),
+    )
+}
@@ -0,0 +1,138 @@
+use crate::capabilities::CAPABILITIES;
+use crate::errors::ContainerError;
+use crate::hooks::HOOK_TYPES;
+use crate::namespaces::NAMESPACE_TYPES;
+use crate::oci::{OCI_VERSION_MAX, OCI_VERSION_MIN};
+use crate::types::{mount_option, MountOption, MOUNT_OPTIONS};
+use serde::Serialize;
+
+// what frogc supports, in the format of the OCI runtime features document
+// everything is derived from the tables the rest of frogc works with, so the document cannot drift from the code
+#[derive(Serialize)]
+pub struct Features {
+    #[serde(rename = "ociVersionMin")]
+    pub oci_version_min: String,
+
+    #[serde(rename = "ociVersionMax")]
+    pub oci_version_max: String,
+
+    #[serde(rename = "hooks")]
+    pub hooks: Vec<String>,
+
+    #[serde(rename = "mountOptions")]
+    pub mount_options: Vec<String>,
+
+    #[serde(rename = "linux")]
+    pub linux: LinuxFeatures,
+}
+
+#[derive(Serialize)]
+pub struct LinuxFeatures {
+    #[serde(rename = "namespaces")]
+    pub namespaces: Vec<String>,
+
+    // only process.capabilities.bounding is applied, see oci::Capabilities
+    #[serde(rename = "capabilities")]
+    pub capabilities: Vec<String>,
+
+    #[serde(rename = "cgroup")]
+    pub cgroup: CgroupFeatures,
+
+    #[serde(rename = "seccomp")]
+    pub seccomp: SeccompFeatures,
+
+    #[serde(rename = "apparmor")]
+    pub apparmor: Enabled,
+
+    #[serde(rename = "selinux")]
+    pub selinux: Enabled,
+
+    #[serde(rename = "mountExtensions")]
+    pub mount_extensions: MountExtensions,
+}
+
+#[derive(Serialize)]
+pub struct CgroupFeatures {
+    #[serde(rename = "v1")]
+    pub v1: bool,
+
+    #[serde(rename = "v2")]
+    pub v2: bool,
+
+    #[serde(rename = "systemd")]
+    pub systemd: bool,
+
+    #[serde(rename = "systemdUser")]
+    pub systemd_user: bool,
+
+    #[serde(rename = "rdma")]
+    pub rdma: bool,
+}
+
+#[derive(Serialize)]
+pub struct SeccompFeatures {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+
+    #[serde(rename = "actions")]
+    pub actions: Vec<String>,
+
+    #[serde(rename = "archs")]
+    pub archs: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MountExtensions {
+    #[serde(rename = "idmap")]
+    pub idmap: Enabled,
+}
+
+#[derive(Serialize)]
+pub struct Enabled {
+    #[serde(rename = "enabled")]
+    pub enabled: bool,
+}
+
+fn features() -> Features {
+    let mount_options = MOUNT_OPTIONS
+        .iter()
+        .filter(|(_, effect)| !matches!(effect, MountOption::Unsupported))
+        .map(|(option, _)| option.to_string())
+        .collect();
+
+    Features {
+        oci_version_min: OCI_VERSION_MIN.to_string(),
+        oci_version_max: OCI_VERSION_MAX.to_string(),
+        hooks: HOOK_TYPES.iter().map(|hook_type| hook_type.to_string()).collect(),
+        mount_options,
+        linux: LinuxFeatures {
+            namespaces: NAMESPACE_TYPES.iter().map(|ns| ns.to_string()).collect(),
+            capabilities: CAPABILITIES.iter().map(|cap| cap.to_string()).collect(),
+            cgroup: CgroupFeatures {
+                v1: false,
+                v2: true,
+                systemd: false,
+                systemd_user: false,
+                rdma: false,
+            },
+            seccomp: SeccompFeatures {
+                enabled: false,
+                actions: vec![],
+                archs: vec![],
+            },
+            apparmor: Enabled { enabled: false },
+            selinux: Enabled { enabled: false },
+            mount_extensions: MountExtensions {
+                idmap: Enabled {
+                    enabled: !matches!(mount_option("idmap"), None | Some(MountOption::Unsupported)),
+                },
+            },
+        },
+    }
+}
+
+pub fn run() -> Result<(), ContainerError> {
+    println!("{}", serde_json::to_string_pretty(&features()).map_err(ContainerError::wrap)?);
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use crate::commands::spec;
+use crate::errors::{ContainerError, WrapError};
+use crate::image::{ExecutionConfig, Layout};
+use crate::spec::{ContainerSpec, User};
+use std::path::Path;
+use std::{fs, io};
+
+// unpacks an image of an OCI image layout into dest, which must not exist or be empty
+// with spec_output a spec running the image's command in dest is written as well, "-" writes it to stdout
+pub fn unpack(image: String, dest: String, spec_output: Option<String>) -> Result<(), ContainerError> {
+    let (layout_dir, tag) = parse_image(&image);
+    let layout = Layout::new(layout_dir);
+    let manifest = layout
+        .manifest(tag)
+        .map_err(WrapError::wrapper(format!("resolving image {}", image).as_str()))
+        .map_err(ContainerError::wrap)?;
+    // the config is read first, so a broken image fails before anything is unpacked
+    let config = layout
+        .config(&manifest)
+        .map_err(WrapError::wrapper("reading image config"))
+        .map_err(ContainerError::wrap)?
+        .config
+        .unwrap_or_default();
+
+    let dest = Path::new(&dest);
+    prepare_dest(dest)
+        .map_err(WrapError::wrapper(format!("preparing {}", dest.display()).as_str()))
+        .map_err(ContainerError::wrap)?;
+    layout
+        .unpack(&manifest, dest)
+        .map_err(WrapError::wrapper("unpacking image"))
+        .map_err(ContainerError::wrap)?;
+
+    let Some(spec_output) = spec_output else {
+        return Ok(());
+    };
+    let rootfs = fs::canonicalize(dest)
+        .map_err(ContainerError::wrap)?
+        .to_string_lossy()
+        .to_string();
+    let spec = image_spec(rootfs, &config, dest)
+        .map_err(WrapError::wrapper("generating spec"))
+        .map_err(ContainerError::wrap)?;
+    spec::write(&spec, (spec_output != "-").then_some(spec_output))
+}
+
+// <layout-dir>[:tag], a colon in a directory name is only taken as the tag separator if no / follows it
+fn parse_image(image: &str) -> (&str, Option<&str>) {
+    match image.rsplit_once(':') {
+        Some((dir, tag)) if !dir.is_empty() && !tag.is_empty() && !tag.contains('/') => (dir, Some(tag)),
+        _ => (image, None),
+    }
+}
+
+fn prepare_dest(dest: &Path) -> io::Result<()> {
+    fs::create_dir_all(dest)?;
+    if fs::read_dir(dest)?.next().is_some() {
+        return Err(io::Error::other("the directory is not empty"));
+    }
+    Ok(())
+}
+
+// the default spec with what the image configures, like docker the entrypoint is followed by the cmd
+fn image_spec(rootfs: String, config: &ExecutionConfig, dest: &Path) -> io::Result<ContainerSpec> {
+    let mut spec = spec::default_spec(rootfs, false);
+
+    let cmd: Vec<String> = config
+        .entrypoint
+        .iter()
+        .flatten()
+        .chain(config.cmd.iter().flatten())
+        .cloned()
+        .collect();
+    if !cmd.is_empty() {
+        spec.cmd = cmd;
+    }
+
+    if let Some(image_env) = &config.env {
+        let mut env = image_env.clone();
+        // images usually set PATH, those that do not still need one to find the command
+        if !env.iter().any(|var| var.starts_with("PATH=")) {
+            let default_path = spec.env.iter().flatten().find(|var| var.starts_with("PATH="));
+            env.extend(default_path.cloned());
+        }
+        spec.env = Some(env);
+    }
+
+    if let Some(working_dir) = config.working_dir.as_ref().filter(|dir| !dir.is_empty()) {
+        spec.work_dir = Some(working_dir.clone());
+    }
+
+    if let Some(user) = config.user.as_ref().filter(|user| !user.is_empty()) {
+        spec.user = Some(resolve_user(dest, user)?);
+    }
+
+    Ok(spec)
+}
+
+// User is uid, name, uid:gid, name:group and so on, names are looked up in the image's /etc/passwd and /etc/group
+// without a group the user's primary group from /etc/passwd is used
+fn resolve_user(rootfs: &Path, user: &str) -> io::Result<User> {
+    let (name, group) = match user.split_once(':') {
+        Some((name, group)) => (name, Some(group)),
+        None => (user, None),
+    };
+
+    // name:password:uid:gid:...
+    let passwd = read_etc(rootfs, "passwd")?;
+    let entry = find_entry(&passwd, name);
+
+    let uid = match (name.parse(), &entry) {
+        (Ok(uid), _) => uid,
+        (Err(_), Some(fields)) => parse_id(fields[2])?,
+        (Err(_), None) => return Err(io::Error::other(format!("user {} not found in /etc/passwd", name))),
+    };
+
+    // name:password:gid:members
+    let gid = match (group.map(str::parse), group) {
+        (Some(Ok(gid)), _) => gid,
+        (Some(Err(_)), Some(group)) => {
+            let groups = read_etc(rootfs, "group")?;
+            let fields = find_entry(&groups, group)
+                .ok_or_else(|| io::Error::other(format!("group {} not found in /etc/group", group)))?;
+            parse_id(fields[2])?
+        }
+        _ => match entry {
+            Some(fields) => parse_id(fields[3])?,
+            None => 0,
+        },
+    };
+
+    Ok(User {
+        uid,
+        gid,
+        additional_gids: None,
+    })
+}
+
+// the entry of /etc/passwd or /etc/group with the name, or with the id for a numeric name
+fn find_entry<'a>(content: &'a str, name: &str) -> Option<Vec<&'a str>> {
+    content
+        .lines()
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .find(|fields| fields.len() > 3 && (fields[0] == name || fields[2] == name))
+}
+
+fn parse_id(id: &str) -> io::Result<u32> {
+    id.parse().map_err(|_| io::Error::other(format!("invalid id {}", id)))
+}
+
+// a symlink in the image would be resolved on the host, so it is treated like a missing file
+fn read_etc(rootfs: &Path, file: &str) -> io::Result<String> {
+    for path in [rootfs.join("etc"), rootfs.join("etc").join(file)] {
+        match fs::symlink_metadata(&path) {
+            Ok(meta) if !meta.is_symlink() => (),
+            Ok(_) => return Ok(String::new()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(String::new()),
+            Err(e) => return Err(e),
+        }
+    }
+    fs::read_to_string(rootfs.join("etc").join(file))
+}
@@ -0,0 +1,88 @@
+use crate::cgroup::Cgroup;
+use crate::context::FrogContext;
+use crate::errors::ContainerError;
+use crate::shim;
+use crate::shim::Request;
+use nix::errno::Errno;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::io;
+use std::str::FromStr;
+
+pub fn run(context: FrogContext, container_id: String, signal: String, all: bool) -> Result<(), ContainerError> {
+    let signal = parse_signal(&signal)?;
+
+    let _lock = context.lookup_container(&container_id)?;
+
+    let state = context
+        .read_state(&container_id)
+        .map_err(ContainerError::wrap)?;
+    match state.status.as_str() {
+        // a created container's process waits for start and can be signalled like a running one
+        "running" | "created" => (),
+        // frozen processes only act on SIGKILL, anything else would stay pending until the container is resumed
+        "paused" if signal == Signal::SIGKILL => (),
+        "paused" => {
+            return Err(ContainerError::InvalidState(
+                "Container is paused, only SIGKILL can be delivered".to_string(),
+            ));
+        }
+        _ => {
+            return Err(ContainerError::InvalidState(
+                "Container is not running".to_string(),
+            ));
+        }
+    }
+
+    if all {
+        return signal_all(&container_id, state.pid, signal);
+    }
+
+    // the shim knows whether its child is still alive, so it never signals a pid that has been reused
+    let request = Request::Kill { signal: signal as i32 };
+    if let Some(response) = shim::request(&context, &container_id, &request).map_err(ContainerError::wrap)? {
+        response.into_result()?;
+        return Ok(());
+    }
+
+    let pid = state.pid.ok_or(ContainerError::InvalidState(
+        "Container has no process".to_string(),
+    ))?;
+    kill(Pid::from_raw(pid as i32), signal).map_err(ContainerError::wrap)?;
+
+    Ok(())
+}
+
+// signals every process in the container's cgroup, or only the container process on hosts without cgroup v2
+pub fn signal_all(container_id: &str, pid: Option<u32>, signal: Signal) -> Result<(), ContainerError> {
+    let cgroup = Cgroup::new(container_id);
+    let pids = if Cgroup::is_supported() && cgroup.exists() {
+        cgroup.processes().map_err(ContainerError::wrap)?
+    } else {
+        pid.map(|pid| Pid::from_raw(pid as i32)).into_iter().collect()
+    };
+
+    for pid in pids {
+        match kill(pid, signal) {
+            // the process exited since the cgroup was read
+            Err(Errno::ESRCH) => (),
+            r => r.map_err(ContainerError::wrap)?,
+        }
+    }
+
+    Ok(())
+}
+
+// accepts signal names with or without the SIG prefix as well as signal numbers
+pub fn parse_signal(signal: &str) -> Result<Signal, ContainerError> {
+    let parsed = match signal.parse::<i32>() {
+        Ok(n) => Signal::try_from(n).ok(),
+        Err(_) => {
+            let name = signal.to_uppercase();
+            let name = if name.starts_with("SIG") { name } else { format!("SIG{}", name) };
+            Signal::from_str(&name).ok()
+        }
+    };
+
+    parsed.ok_or_else(|| ContainerError::wrap(io::Error::other(format!("unknown signal {}", signal))))
+}
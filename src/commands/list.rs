@@ -0,0 +1,100 @@
+use crate::cli::OutputFormat;
+use crate::context::FrogContext;
+use crate::errors::ContainerError;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct ContainerSummary {
+    #[serde(rename = "id")]
+    id: String,
+
+    #[serde(rename = "status")]
+    status: String,
+
+    #[serde(rename = "pid")]
+    pid: Option<u32>,
+
+    #[serde(rename = "exitCode")]
+    exit_code: Option<i32>,
+
+    #[serde(rename = "exitSignal")]
+    exit_signal: Option<String>,
+
+    #[serde(rename = "oomKilled")]
+    oom_killed: Option<bool>,
+
+    #[serde(rename = "finishedAt")]
+    finished_at: Option<DateTime<Utc>>,
+}
+
+pub fn run(context: FrogContext, format: OutputFormat) -> Result<(), ContainerError> {
+    let mut containers = vec![];
+    for container_id in context.list_containers().map_err(ContainerError::wrap)? {
+        // deleted since it was listed
+        let _lock = match context.lookup_container(&container_id) {
+            Ok(lock) => lock,
+            Err(ContainerError::NotFound) => continue,
+            Err(e) => return Err(e),
+        };
+
+        // a broken container should not hide all the others
+        let state = match context.read_state(&container_id) {
+            Ok(state) => state,
+            Err(e) => {
+                log::warn!("reading state of {}: {}", container_id, e);
+                continue;
+            }
+        };
+
+        containers.push(ContainerSummary {
+            id: state.id,
+            status: state.status,
+            pid: state.pid,
+            exit_code: state.exit_code,
+            exit_signal: state.exit_signal,
+            oom_killed: state.oom_killed,
+            finished_at: state.finished_at,
+        });
+    }
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&containers).map_err(ContainerError::wrap)?);
+        }
+        OutputFormat::Table => {
+            println!("{:<24} {:<10} {:<10} {:<24} FINISHED", "ID", "STATUS", "PID", "EXIT");
+            for c in containers {
+                let pid = c.pid.map(|pid| pid.to_string()).unwrap_or("-".to_string());
+                let finished = c
+                    .finished_at
+                    .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+                    .unwrap_or("-".to_string());
+                println!("{:<24} {:<10} {:<10} {:<24} {}", c.id, c.status, pid, exit_description(&c), finished);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// e.g. "0", "137 (SIGKILL)" or "137 (SIGKILL, OOM killed)"
+fn exit_description(c: &ContainerSummary) -> String {
+    let Some(code) = c.exit_code else {
+        return "-".to_string();
+    };
+
+    let mut details = vec![];
+    if let Some(signal) = &c.exit_signal {
+        details.push(signal.clone());
+    }
+    if c.oom_killed == Some(true) {
+        details.push("OOM killed".to_string());
+    }
+
+    if details.is_empty() {
+        code.to_string()
+    } else {
+        format!("{} ({})", code, details.join(", "))
+    }
+}
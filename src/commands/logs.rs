@@ -0,0 +1,136 @@
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::logging;
+use crate::logging::LogEntry;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+use std::{fs, io};
+
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+pub fn run(
+    context: FrogContext,
+    container_id: String,
+    follow: bool,
+    tail: Option<usize>,
+) -> Result<(), ContainerError> {
+    let state = {
+        let _lock = context.lookup_container(&container_id)?;
+
+        context
+            .read_state(&container_id)
+            .map_err(ContainerError::wrap)?
+    };
+
+    let config = state.spec.log.ok_or(ContainerError::InvalidState(
+        "Container has no log configured".to_string(),
+    ))?;
+    let path = logging::log_path(&context, &container_id, &config);
+
+    // rotated files hold older output, so they are printed first, from the oldest to the newest
+    let mut lines = vec![];
+    for n in (1..logging::max_files(&config)).rev() {
+        let rotated = logging::rotated_path(&path, n);
+        if rotated.exists() {
+            let content = fs::read(&rotated)
+                .map_err(WrapError::wrapper("reading rotated log file"))
+                .map_err(ContainerError::wrap)?;
+            lines.extend(String::from_utf8_lossy(&content).lines().map(str::to_string));
+        }
+    }
+
+    let mut current = open_log(&path)?;
+    let mut pending = String::new();
+    read_lines(&mut current, &mut pending, &mut lines).map_err(ContainerError::wrap)?;
+
+    let skip = tail.map(|n| lines.len().saturating_sub(n)).unwrap_or(0);
+    for line in &lines[skip..] {
+        print_entry(line).map_err(ContainerError::wrap)?;
+    }
+
+    if !follow {
+        return Ok(());
+    }
+
+    loop {
+        let mut lines = vec![];
+        let read = read_lines(&mut current, &mut pending, &mut lines).map_err(ContainerError::wrap)?;
+        for line in &lines {
+            print_entry(line).map_err(ContainerError::wrap)?;
+        }
+
+        if read > 0 {
+            continue;
+        }
+
+        // once the file got rotated, everything new is written to a fresh file at the same path
+        let rotated = match (fs::metadata(&path), current.metadata()) {
+            (Ok(new), Ok(old)) => new.ino() != old.ino() || new.dev() != old.dev(),
+            _ => false,
+        };
+        if rotated {
+            current = open_log(&path)?;
+            pending.clear();
+            continue;
+        }
+
+        let running = context
+            .read_state(&container_id)
+            .map(|state| state.status != "stopped")
+            .unwrap_or(true);
+        if !running {
+            return Ok(());
+        }
+
+        sleep(FOLLOW_INTERVAL);
+    }
+}
+
+fn open_log(path: &Path) -> Result<File, ContainerError> {
+    match File::open(path) {
+        Ok(file) => Ok(file),
+        // the log is only created once the container has been started
+        // until then we read from /dev/null, the check for rotation picks up the file once it appears
+        Err(e) if e.kind() == io::ErrorKind::NotFound => File::open("/dev/null").map_err(ContainerError::wrap),
+        Err(e) => Err(ContainerError::wrap(WrapError::wrapper("opening log file")(e))),
+    }
+}
+
+// reads everything appended to the file since the last call and collects the complete lines
+// an incomplete line at the end (the writer may be in the middle of it) is kept in pending for the next call
+// the container may write arbitrary bytes, so invalid UTF-8 is replaced rather than treated as an error
+fn read_lines(file: &mut File, pending: &mut String, lines: &mut Vec<String>) -> io::Result<usize> {
+    let mut content = vec![];
+    let read = file.read_to_end(&mut content)?;
+    pending.push_str(&String::from_utf8_lossy(&content));
+
+    if let Some(end) = pending.rfind('\n') {
+        let rest = pending.split_off(end + 1);
+        lines.extend(pending.lines().map(str::to_string));
+        *pending = rest;
+    }
+
+    Ok(read)
+}
+
+fn print_entry(line: &str) -> io::Result<()> {
+    let Some(entry) = LogEntry::parse(line) else {
+        log::warn!("skipping malformed log line: {}", line);
+        return Ok(());
+    };
+
+    let newline = if entry.partial { "" } else { "\n" };
+    if entry.stream == "stderr" {
+        let mut stderr = io::stderr().lock();
+        write!(stderr, "{}{}", entry.message, newline)?;
+        stderr.flush()
+    } else {
+        let mut stdout = io::stdout().lock();
+        write!(stdout, "{}{}", entry.message, newline)?;
+        stdout.flush()
+    }
+}
@@ -1,4 +1,21 @@
 pub mod create;
 pub mod start;
 pub mod reexec_start;
-
+pub mod pause;
+pub mod resume;
+pub mod stats;
+pub mod events;
+pub mod ps;
+pub mod run;
+pub mod logs;
+pub mod state;
+pub mod list;
+pub mod kill;
+pub mod resize;
+pub mod attach;
+pub mod delete;
+pub mod exec;
+pub mod features;
+pub mod validate;
+pub mod spec;
+pub mod image;
@@ -0,0 +1,173 @@
+use crate::cgroup::Cgroup;
+use crate::cli::OutputFormat;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use nix::unistd::Pid;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::{fs, io};
+
+#[derive(Serialize)]
+struct ProcessInfo {
+    #[serde(rename = "pid")]
+    pid: i32,
+
+    #[serde(rename = "containerPid")]
+    container_pid: Option<i32>,
+
+    #[serde(rename = "user")]
+    user: String,
+
+    #[serde(rename = "cmd")]
+    cmd: Vec<String>,
+}
+
+pub fn run(context: FrogContext, container_id: String, format: OutputFormat) -> Result<(), ContainerError> {
+    let state = {
+        let _lock = context.lookup_container(&container_id)?;
+
+        context
+            .read_state(&container_id)
+            .map_err(ContainerError::wrap)?
+    };
+
+    let pid = match state.pid {
+        Some(pid) if state.status == "running" || state.status == "paused" => Pid::from_raw(pid as i32),
+        _ => {
+            return Err(ContainerError::InvalidState(
+                "Container is not running".to_string(),
+            ))
+        }
+    };
+
+    // the cgroup knows every process of the container, without one we fall back to the process tree below the container pid
+    let cgroup = Cgroup::new(&container_id);
+    let pids = if cgroup.exists() {
+        cgroup.processes()
+    } else {
+        descendants(pid)
+    }
+    .map_err(WrapError::wrapper("listing container processes"))
+    .map_err(ContainerError::wrap)?;
+
+    // user names are resolved against the container's own /etc/passwd, not the host's
+    let users = read_passwd(pid);
+
+    // processes may exit while we are looking at them, those are skipped
+    let processes: Vec<ProcessInfo> = pids
+        .into_iter()
+        .filter_map(|pid| process_info(pid, &users).ok())
+        .collect();
+
+    match format {
+        OutputFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&processes).map_err(ContainerError::wrap)?);
+        }
+        OutputFormat::Table => {
+            println!("{:<10} {:<10} {:<12} CMD", "PID", "CPID", "USER");
+            for p in processes {
+                let container_pid = p.container_pid.map(|pid| pid.to_string()).unwrap_or("-".to_string());
+                println!("{:<10} {:<10} {:<12} {}", p.pid, container_pid, p.user, p.cmd.join(" "));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn process_info(pid: Pid, users: &HashMap<u32, String>) -> io::Result<ProcessInfo> {
+    let status = fs::read_to_string(format!("/proc/{}/status", pid))?;
+
+    // NSpid lists the pid in every nested pid namespace, the last one is the pid inside the container
+    let container_pid = status_field(&status, "NSpid")
+        .and_then(|v| v.split_whitespace().last())
+        .and_then(|v| v.parse().ok());
+
+    // Uid lists the real, effective, saved and filesystem uid, we report the effective one
+    let uid: Option<u32> = status_field(&status, "Uid")
+        .and_then(|v| v.split_whitespace().nth(1))
+        .and_then(|v| v.parse().ok());
+    let user = match uid {
+        Some(uid) => users.get(&uid).cloned().unwrap_or(uid.to_string()),
+        None => "?".to_string(),
+    };
+
+    // kernel threads and zombies have an empty cmdline, ps shows their name in brackets instead
+    let cmdline = fs::read(format!("/proc/{}/cmdline", pid))?;
+    let mut cmd: Vec<String> = cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect();
+    if cmd.is_empty() {
+        let name = status_field(&status, "Name").unwrap_or("?");
+        cmd.push(format!("[{}]", name));
+    }
+
+    Ok(ProcessInfo {
+        pid: pid.as_raw(),
+        container_pid,
+        user,
+        cmd,
+    })
+}
+
+fn status_field<'a>(status: &'a str, key: &str) -> Option<&'a str> {
+    status.lines().find_map(|line| {
+        let (k, v) = line.split_once(':')?;
+        (k == key).then(|| v.trim())
+    })
+}
+
+fn descendants(root: Pid) -> io::Result<Vec<Pid>> {
+    let mut children: HashMap<i32, Vec<i32>> = HashMap::new();
+
+    for entry in fs::read_dir("/proc")? {
+        let Some(pid) = entry?.file_name().to_str().and_then(|name| name.parse::<i32>().ok()) else {
+            continue;
+        };
+
+        // the comm field may contain spaces and parentheses, so we parse from the last closing parenthesis
+        // the fields after it are: state, ppid, ...
+        let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+            continue;
+        };
+        let ppid = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+            .and_then(|ppid| ppid.parse().ok());
+
+        if let Some(ppid) = ppid {
+            children.entry(ppid).or_default().push(pid);
+        }
+    }
+
+    let mut result = vec![];
+    let mut queue = vec![root.as_raw()];
+    while let Some(pid) = queue.pop() {
+        result.push(Pid::from_raw(pid));
+        if let Some(c) = children.get(&pid) {
+            queue.extend(c);
+        }
+    }
+
+    result.sort();
+    Ok(result)
+}
+
+fn read_passwd(pid: Pid) -> HashMap<u32, String> {
+    let Ok(passwd) = fs::read_to_string(format!("/proc/{}/root/etc/passwd", pid)) else {
+        return HashMap::new();
+    };
+
+    // name:password:uid:gid:gecos:home:shell
+    passwd
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
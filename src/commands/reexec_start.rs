@@ -1,35 +1,170 @@
+use crate::capabilities;
+use crate::commands::start;
+use crate::console;
 use crate::context::FrogContext;
+use crate::devices;
+use crate::etc;
 use crate::errors::{ContainerError, WrapError};
-use crate::spec::{ContainerSpec, ContainerState};
+use crate::hooks;
+use crate::namespaces;
+use crate::oci;
+use crate::paths;
+use crate::rootfs;
+use crate::init;
+use crate::shim;
+use crate::spec::{ContainerState, User};
+use crate::sysctl;
 use nix::libc;
-use nix::libc::{mount_attr, O_CLOEXEC, O_DIRECTORY, O_PATH};
+use nix::fcntl::{fcntl, AtFlags, FcntlArg, FdFlag};
+use nix::libc::{O_CLOEXEC, O_DIRECTORY, O_PATH};
 use nix::mount::{mount, umount2, MntFlags, MsFlags};
-use nix::sys::stat::{mknod, Mode};
-use nix::unistd::{chdir, execvp, fchdir, pivot_root};
+use nix::sys::stat::{fstat, SFlag};
+use nix::unistd::{access, chdir, execve, execvp, fchdir, pivot_root, setgid, setgroups, setuid, AccessFlags, Gid, Uid};
 use std::ffi::CString;
-use std::fs::OpenOptions;
+use std::fs::{self, File, OpenOptions};
+use std::{io, process};
+use std::io::Write;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStringExt;
 use std::os::unix::fs::OpenOptionsExt;
-use crate::types;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use crate::syscall::{self, mount_setattr, move_mount, open_tree};
 use crate::types::Mount;
 
-pub fn run(context: FrogContext, container_id: String) -> Result<(), ContainerError> {
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+pub fn run(context: FrogContext, container_id: String, sync_fd: Option<RawFd>) -> Result<(), ContainerError> {
+    // the sync pipe must not leak into the container command
+    let mut sync = sync_fd.map(|fd| {
+        let sync = unsafe { File::from_raw_fd(fd) };
+        let _ = fcntl(&sync, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC));
+        sync
+    });
+
+    let result = run_container(&context, &container_id, &mut sync);
+
+    // create is still waiting on the sync pipe and reports the error to its caller, so we do not print it as well
+    if let (Err(e), Some(sync)) = (&result, &mut sync) {
+        let _ = write!(sync, "{}", e);
+        process::exit(1);
+    }
+
+    result
+}
+
+fn run_container(context: &FrogContext, container_id: &str, sync: &mut Option<File>) -> Result<(), ContainerError> {
     let state = context
-        .read_state(&container_id)
+        .read_state(container_id)
         .map_err(WrapError::wrapper("reading container state"))
         .map_err(ContainerError::wrap)?;
 
-    setup_mounts(&state)?;
+    namespaces::enter(&state.spec)
+        .map_err(WrapError::wrapper("entering namespaces"))
+        .map_err(ContainerError::wrap)?;
+
+    // the console socket lives on the host, so we have to connect before pivoting into the rootfs
+    // without a console socket given to create, the shim listens on one in the container run dir
+    let console_socket = if state.spec.terminal.unwrap_or(false) {
+        let path = match &state.console_socket {
+            Some(path) => PathBuf::from(path),
+            None => shim::console_socket_path(context, container_id),
+        };
+        Some(
+            UnixStream::connect(path)
+                .map_err(WrapError::wrapper("connecting to console socket"))
+                .map_err(ContainerError::wrap)?,
+        )
+    } else {
+        None
+    };
+
+    // a container created from a bundle waits on the exec fifo until it is started
+    // the fifo is in the run dir, so it is opened before pivoting and reopened through /proc/self/fd afterwards
+    let exec_fifo = if state.status == "created" {
+        Some(
+            OpenOptions::new()
+                .read(true)
+                .custom_flags(O_PATH | O_CLOEXEC)
+                .open(start::exec_fifo_path(context, container_id))
+                .map_err(WrapError::wrapper("opening exec fifo"))
+                .map_err(ContainerError::wrap)?,
+        )
+    } else {
+        None
+    };
+
+    // like the fifo, the generated /etc files are in the run dir, which is out of reach after pivoting
+    // containers created from a bundle do not get them
+    let etc_dir = if state.bundle.is_none() {
+        Some(
+            OpenOptions::new()
+                .read(true)
+                .custom_flags(O_PATH | O_DIRECTORY | O_CLOEXEC)
+                .open(etc::dir(context, container_id))
+                .map_err(WrapError::wrapper("opening /etc files"))
+                .map_err(ContainerError::wrap)?,
+        )
+    } else {
+        None
+    };
+
+    setup_mounts(&state, etc_dir.as_ref().map(|dir| dir.as_fd()), console_socket.as_ref())?;
+
+    // without its own UTS namespace the container would rename the host
+    if namespaces::is_private(&state.spec, "uts") {
+        nix::unistd::sethostname(state.spec.hostname.as_deref().unwrap_or(container_id))
+            .map_err(WrapError::wrapper("setting hostname"))
+            .map_err(ContainerError::wrap)?;
+        syscall::setdomainname(state.spec.domainname.as_deref().unwrap_or(container_id))
+            .map_err(WrapError::wrapper("setting domainname"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    if let Some(exec_fifo) = exec_fifo {
+        wait_for_start(exec_fifo, sync.take())?;
+    }
+
+    hooks::run(&state.spec, "startContainer", &oci::State::new(&state))
+        .map_err(ContainerError::wrap)?;
+
+    if state.spec.init.unwrap_or(false) {
+        let terminal = state.spec.terminal.unwrap_or(false);
+        return init::run(terminal, || exec_spec_process(&state));
+    }
+
+    exec_spec_process(&state)
+}
 
-    nix::unistd::sethostname(container_id.clone())
-        .map_err(WrapError::wrapper("setting hostname"))
-        .unwrap();
+fn exec_spec_process(state: &ContainerState) -> Result<(), ContainerError> {
+    let spec = &state.spec;
+    exec_process(&spec.cmd, spec.env.as_deref(), spec.user.as_ref(), spec.capabilities.as_deref())
+}
 
-    exec_container(state.spec);
+// tells create that the setup succeeded and blocks until start opens the exec fifo
+fn wait_for_start(exec_fifo: File, sync: Option<File>) -> Result<(), ContainerError> {
+    if let Some(mut sync) = sync {
+        sync.write_all(b"ok")
+            .map_err(WrapError::wrapper("reporting to create"))
+            .map_err(ContainerError::wrap)?;
+    }
 
-    unreachable!()
+    let mut fifo = OpenOptions::new()
+        .write(true)
+        .custom_flags(O_CLOEXEC)
+        .open(format!("/proc/self/fd/{}", exec_fifo.as_raw_fd()))
+        .map_err(WrapError::wrapper("reopening exec fifo"))
+        .map_err(ContainerError::wrap)?;
+    fifo.write_all(&[0])
+        .map_err(WrapError::wrapper("writing exec fifo"))
+        .map_err(ContainerError::wrap)
 }
 
-fn setup_mounts(state: &ContainerState) -> Result<(), ContainerError> {
+fn setup_mounts(
+    state: &ContainerState,
+    etc_dir: Option<BorrowedFd>,
+    console_socket: Option<&UnixStream>,
+) -> Result<(), ContainerError> {
     // when calling copy the child process inherits the mount information
     // we want to make the mounts private so that any changes are isolated/not being propagated to the parent
     mount(
@@ -42,6 +177,21 @@ fn setup_mounts(state: &ContainerState) -> Result<(), ContainerError> {
     .map_err(WrapError::wrapper("making mounts private"))
     .map_err(ContainerError::wrap)?;
 
+    // pivot_root requires the new root to be a mount point, which a plain directory is not
+    // bind mounting the rootfs onto itself turns it into one (and is a no-op for an already mounted rootfs)
+    let rootfs = rootfs::path(state)
+        .map_err(WrapError::wrapper("resolving rootfs"))
+        .map_err(ContainerError::wrap)?;
+    mount(
+        Some(rootfs),
+        rootfs,
+        None::<&str>,
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        None::<&str>,
+    )
+    .map_err(WrapError::wrapper("bind mounting rootfs"))
+    .map_err(ContainerError::wrap)?;
+
     // we prepare the pivot_root call by acquiring file descriptors for the old and new root directory
     // we must use the flags below so that we open the directory and get the file descriptor
     let old_root = OpenOptions::new()
@@ -54,7 +204,7 @@ fn setup_mounts(state: &ContainerState) -> Result<(), ContainerError> {
     let new_root = OpenOptions::new()
         .custom_flags(O_PATH | O_DIRECTORY | O_CLOEXEC)
         .read(true)
-        .open(state.spec.rootfs.as_str())
+        .open(rootfs)
         .map_err(WrapError::wrapper("opening new root"))
         .map_err(ContainerError::wrap)?;
 
@@ -62,6 +212,28 @@ fn setup_mounts(state: &ContainerState) -> Result<(), ContainerError> {
         .map_err(WrapError::wrapper("changing working directory to rootfs"))
         .map_err(ContainerError::wrap)?;
 
+    // the hooks run in the container's namespaces while the host's filesystem is still what they see
+    hooks::run(&state.spec, "createContainer", &oci::State::new(state).with_status("creating"))
+        .map_err(ContainerError::wrap)?;
+
+    // bind sources are host paths, after pivoting they would resolve inside the rootfs
+    // so they are cloned now, after the hooks which may have created them, and attached once the rootfs is in place
+    let mounts: Vec<Mount> = state.spec.mounts.iter().map(Mount::from).collect();
+    let mut bind_sources = Vec::with_capacity(mounts.len());
+    for m in &mounts {
+        let source = if m.is_bind() {
+            let relative = m.source.strip_prefix('/').unwrap_or(&m.source);
+            Some(
+                open_tree(old_root.as_fd(), relative, m.flags.contains(MsFlags::MS_REC))
+                    .map_err(WrapError::wrapper(format!("opening bind source {}", m.source).as_str()))
+                    .map_err(ContainerError::wrap)?,
+            )
+        } else {
+            None
+        };
+        bind_sources.push(source);
+    }
+
     // we use pivot_root to set up the new root fs
     // since we have chdir'd into the rootfs directory we can use "." to refer to it
     // passing in "." and "." effectively layers the old and new rootfs on top of each other
@@ -72,28 +244,13 @@ fn setup_mounts(state: &ContainerState) -> Result<(), ContainerError> {
         .map_err(WrapError::wrapper("pivoting root"))
         .map_err(ContainerError::wrap)?;
 
-    // we change the working directory to the old root fs using the file descriptor
-    // this is necessary because the following umount call does not work with file descriptors, only with paths
-    fchdir(&old_root)
-        .map_err(WrapError::wrapper(
-            "changing working directory to old rootfs",
-        ))
-        .map_err(ContainerError::wrap)?;
-
-    // unmount the old root with umount2 because we need to use MNT_DETACH to lazily unmount the filesystem
-    // "." refers to the current working directory, aka the old root
-    // otherwise the umount call will fail with EBUSY
-    // this is fine as the container will not have any access to the original filesystem anymore
-    umount2(".", MntFlags::MNT_DETACH)
-        .map_err(WrapError::wrapper("umounting old root"))
-        .map_err(ContainerError::wrap)?;
-
-    // we change the working directory back to the new rootfs so all the following mount calls will mount in the correct fs
+    // the old root stays attached until the container's mounts are set up
+    // the kernel only lets a user namespace mount procfs if a fully visible procfs already exists in the mount namespace
     fchdir(&new_root)
         .map_err(WrapError::wrapper("changing working directory to rootfs"))
         .map_err(ContainerError::wrap)?;
 
-    // we now mount new rootfs with shared settings, since it can no longer affect the old root
+    // we now mount new rootfs with shared settings, the old root below it was made private so this cannot affect the host
     // we do this because some software expects this
     mount(
         None::<&str>,
@@ -105,73 +262,95 @@ fn setup_mounts(state: &ContainerState) -> Result<(), ContainerError> {
     .map_err(WrapError::wrapper("making mounts shared"))
     .map_err(ContainerError::wrap)?;
 
-    for m in state.spec.mounts.iter().map(Mount::from) {
-        mount(
-            Some(m.destination.as_str()),
-            m.destination.as_str(),
-            Some(m.fs_type.as_str()),
-            m.flags,
-            m.options.as_deref(),
-        )
+    for (m, bind_source) in mounts.iter().zip(&bind_sources) {
+        if m.tmp_copy_up {
+            log::warn!("tmpcopyup is not supported, ignoring it for {}", m.destination);
+        }
+
+        create_mount_point(m, bind_source.as_ref())
+            .map_err(WrapError::wrapper(format!("creating mount point {}", m.destination).as_str()))
+            .map_err(ContainerError::wrap)?;
+
+        match bind_source {
+            Some(bind_source) => attach_bind(bind_source, m),
+            None => mount(
+                Some(m.source.as_str()),
+                m.destination.as_str(),
+                Some(m.fs_type.as_str()),
+                m.flags,
+                m.options.as_deref(),
+            )
+            .map_err(io::Error::from),
+        }
             .map_err(WrapError::wrapper(format!("mounting {}", m.destination).as_str()))
             .map_err(ContainerError::wrap)?;
+
+        // the recursive options (rro, rnosuid, ...) cannot be expressed with mount(2) and are applied to the whole tree
+        let attr = &m.mount_attr;
+        if !attr.attr_set.is_empty() || !attr.attr_clr.is_empty() {
+            mount_setattr(
+                None,
+                m.destination.as_str(),
+                AtFlags::from_bits_retain(libc::AT_RECURSIVE),
+                attr,
+            )
+                .map_err(WrapError::wrapper(format!("setting mount attributes of {}", m.destination).as_str()))
+                .map_err(ContainerError::wrap)?;
+        }
+
+        if let Some(propagation) = m.propagation {
+            mount(None::<&str>, m.destination.as_str(), None::<&str>, propagation, None::<&str>)
+                .map_err(WrapError::wrapper(format!("setting propagation of {}", m.destination).as_str()))
+                .map_err(ContainerError::wrap)?;
+        }
     }
 
-    mknod(
-        "/dev/null",
-        nix::sys::stat::SFlag::S_IFCHR,
-        Mode::from_bits_truncate(0o777),
-        libc::makedev(1, 3),
-    )
-    .map_err(WrapError::wrapper("creating /dev/null"))
-    .map_err(ContainerError::wrap)?;
+    if let Some(etc_dir) = etc_dir {
+        etc::mount(&state.spec, etc_dir)
+            .map_err(WrapError::wrapper("mounting /etc files"))
+            .map_err(ContainerError::wrap)?;
+    }
 
-    mknod(
-        "/dev/zero",
-        nix::sys::stat::SFlag::S_IFCHR,
-        Mode::from_bits_truncate(0o777),
-        libc::makedev(1, 5),
-    )
-    .map_err(WrapError::wrapper("creating /dev/zero"))
-    .map_err(ContainerError::wrap)?;
+    devices::create(&state.spec, old_root.as_fd())
+        .map_err(WrapError::wrapper("creating devices"))
+        .map_err(ContainerError::wrap)?;
 
-    mknod(
-        "/dev/full",
-        nix::sys::stat::SFlag::S_IFCHR,
-        Mode::from_bits_truncate(0o777),
-        libc::makedev(1, 7),
-    )
-    .map_err(WrapError::wrapper("creating /dev/full"))
-    .map_err(ContainerError::wrap)?;
+    // the console may have to create /dev/console and mount devpts, which a read-only rootfs would refuse
+    if let Some(console_socket) = console_socket {
+        console::setup(console_socket)
+            .map_err(WrapError::wrapper("setting up console"))
+            .map_err(ContainerError::wrap)?;
+    }
 
-    mknod(
-        "/dev/random",
-        nix::sys::stat::SFlag::S_IFCHR,
-        Mode::from_bits_truncate(0o777),
-        libc::makedev(1, 8),
-    )
-    .map_err(WrapError::wrapper("creating /dev/random"))
-    .map_err(ContainerError::wrap)?;
+    // /proc/sys is among the default read-only paths
+    sysctl::apply(&state.spec).map_err(ContainerError::wrap)?;
 
-    mknod(
-        "/dev/urandom",
-        nix::sys::stat::SFlag::S_IFCHR,
-        Mode::from_bits_truncate(0o777),
-        libc::makedev(1, 9),
-    )
-    .map_err(WrapError::wrapper("creating /dev/urandom"))
-    .map_err(ContainerError::wrap)?;
+    paths::protect(&state.spec).map_err(ContainerError::wrap)?;
 
-    mknod(
-        "/dev/tty",
-        nix::sys::stat::SFlag::S_IFCHR,
-        Mode::from_bits_truncate(0o777),
-        libc::makedev(5, 0),
-    )
-    .map_err(WrapError::wrapper("creating /dev/tty"))
-    .map_err(ContainerError::wrap)?;
+    // the devices and the console are created in the rootfs, so this has to wait until they exist
+    if state.spec.rootfs_readonly == Some(true) {
+        paths::remount_readonly(Path::new("/"))
+            .map_err(WrapError::wrapper("making rootfs read-only"))
+            .map_err(ContainerError::wrap)?;
+    }
 
-    // we change the working directory to the container's working directory before unmounting the old root
+    // we change the working directory to the old root fs using the file descriptor
+    // this is necessary because the following umount call does not work with file descriptors, only with paths
+    fchdir(&old_root)
+        .map_err(WrapError::wrapper(
+            "changing working directory to old rootfs",
+        ))
+        .map_err(ContainerError::wrap)?;
+
+    // unmount the old root with umount2 because we need to use MNT_DETACH to lazily unmount the filesystem
+    // "." refers to the current working directory, aka the old root
+    // otherwise the umount call will fail with EBUSY
+    // this is fine as the container will not have any access to the original filesystem anymore
+    umount2(".", MntFlags::MNT_DETACH)
+        .map_err(WrapError::wrapper("umounting old root"))
+        .map_err(ContainerError::wrap)?;
+
+    // the working directory moves from the detached old root to the container's working directory
     chdir(state.spec.work_dir.as_deref().unwrap_or("/"))
         .map_err(WrapError::wrapper("changing working directory"))
         .map_err(ContainerError::wrap)?;
@@ -179,13 +358,180 @@ fn setup_mounts(state: &ContainerState) -> Result<(), ContainerError> {
     Ok(())
 }
 
-fn exec_container(spec: ContainerSpec) {
-    let cmd = CString::new(spec.cmd[0].as_str()).unwrap();
-    let args: Vec<CString> = spec
-        .cmd
+// attaches a bind source cloned before pivoting
+// mount(2) ignores all flags but MS_REC on a bind, ro, nosuid and the like only take effect with a remount
+fn attach_bind(bind_source: &OwnedFd, m: &Mount) -> io::Result<()> {
+    move_mount(bind_source.as_fd(), m.destination.as_str())?;
+
+    let flags = m.flags.difference(MsFlags::MS_BIND | MsFlags::MS_REC);
+    if !flags.is_empty() {
+        paths::remount(Path::new(&m.destination), flags)?;
+    }
+
+    Ok(())
+}
+
+// the destination may not exist yet, e.g. /dev/pts on the fresh /dev tmpfs
+// a file bind mounted needs a file to be mounted over, everything else a directory
+// the source of a bind is only reachable through the tree cloned before pivoting
+fn create_mount_point(m: &Mount, bind_source: Option<&OwnedFd>) -> io::Result<()> {
+    let destination = Path::new(&m.destination);
+    if destination.exists() {
+        return Ok(());
+    }
+
+    let source_is_dir = match bind_source {
+        Some(bind_source) => SFlag::from_bits_truncate(fstat(bind_source)?.st_mode) & SFlag::S_IFMT == SFlag::S_IFDIR,
+        None => true,
+    };
+    if !source_is_dir {
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        File::create(destination)?;
+        return Ok(());
+    }
+
+    fs::create_dir_all(destination)
+}
+
+// switches to the user, drops capabilities and executes the command, used for the container process as well as for exec
+pub fn exec_process(
+    cmd: &[String],
+    env: Option<&[String]>,
+    user: Option<&User>,
+    capabilities: Option<&[String]>,
+) -> Result<(), ContainerError> {
+    let program = cmd
+        .first()
+        .ok_or_else(|| ContainerError::wrap(io::Error::other("the command must not be empty")))?;
+    let args: Vec<CString> = cmd
         .iter()
-        .map(|s| CString::new(s.as_str()).unwrap())
-        .collect();
+        .map(|s| CString::new(s.as_str()))
+        .collect::<Result<_, _>>()
+        .map_err(ContainerError::wrap)?;
+    let program_c = args[0].clone();
+
+    if let Some(capabilities) = capabilities {
+        capabilities::drop_bounding(capabilities)
+            .map_err(WrapError::wrapper("dropping capabilities"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    // the groups have to be dropped while we are still root, the uid last since it takes our privileges
+    if let Some(user) = user {
+        let groups: Vec<Gid> = user
+            .additional_gids
+            .iter()
+            .flatten()
+            .map(|gid| Gid::from_raw(*gid))
+            .collect();
+        setgroups(&groups)
+            .map_err(WrapError::wrapper("setting additional groups"))
+            .map_err(ContainerError::wrap)?;
+        setgid(Gid::from_raw(user.gid))
+            .map_err(WrapError::wrapper("setting gid"))
+            .map_err(ContainerError::wrap)?;
+        setuid(Uid::from_raw(user.uid))
+            .map_err(WrapError::wrapper("setting uid"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    if let Some(capabilities) = capabilities {
+        capabilities::limit(capabilities)
+            .map_err(WrapError::wrapper("dropping capabilities"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    let Some(env) = env else {
+        let Err(e) = execvp(&program_c, &args);
+        return Err(ContainerError::wrap(WrapError::wrapper("executing container command")(e)));
+    };
 
-    execvp(&cmd, &args).unwrap();
+    // execvpe would search the PATH of our own environment, the command has to be found with the container's
+    let env: Vec<CString> = env
+        .iter()
+        .map(|s| CString::new(s.as_str()))
+        .collect::<Result<_, _>>()
+        .map_err(ContainerError::wrap)?;
+    let path = env
+        .iter()
+        .find_map(|var| var.to_str().ok()?.strip_prefix("PATH="))
+        .unwrap_or(DEFAULT_PATH);
+    let program_c = find_executable(program, path).unwrap_or(program_c);
+
+    let Err(e) = execve(&program_c, &args, &env);
+    Err(ContainerError::wrap(WrapError::wrapper("executing container command")(e)))
+}
+
+// resolves a command without a slash against the directories in path
+fn find_executable(cmd: &str, path: &str) -> Option<CString> {
+    if cmd.contains('/') {
+        return None;
+    }
+
+    path.split(':')
+        .map(|dir| Path::new(dir).join(cmd))
+        .find(|candidate| access(candidate, AccessFlags::X_OK).is_ok() && candidate.is_file())
+        .and_then(|candidate| CString::new(candidate.into_os_string().into_vec()).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spec;
+    use nix::fcntl::AT_FDCWD;
+    use nix::sched::{unshare, CloneFlags};
+    use nix::sys::wait::{waitpid, WaitStatus};
+    use nix::unistd::{fork, geteuid, ForkResult};
+    use std::env;
+
+    // mounting needs root and a mount namespace of its own, without root the test has nothing to check
+    // unshare refuses CLONE_NEWNS while other threads share our filesystem information, so a forked child mounts
+    fn in_mount_namespace(test: impl FnOnce() -> bool) -> Option<bool> {
+        if !geteuid().is_root() {
+            return None;
+        }
+
+        match unsafe { fork() }.unwrap() {
+            ForkResult::Child => {
+                let passed = unshare(CloneFlags::CLONE_NEWNS).is_ok()
+                    && mount(None::<&str>, "/", None::<&str>, MsFlags::MS_PRIVATE | MsFlags::MS_REC, None::<&str>).is_ok()
+                    && test();
+                unsafe { libc::_exit(if passed { 0 } else { 1 }) }
+            }
+            ForkResult::Parent { child } => Some(waitpid(child, None).unwrap() == WaitStatus::Exited(child, 0)),
+        }
+    }
+
+    #[test]
+    fn ro_bind_is_read_only() {
+        let dir = env::temp_dir().join(format!("frogc-bind-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let (source, destination) = (dir.join("source"), dir.join("destination"));
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+
+        let spec_mount = spec::Mount {
+            destination: destination.to_string_lossy().to_string(),
+            fs_type: "bind".to_string(),
+            source: source.to_string_lossy().to_string(),
+            rbind: None,
+            options: vec!["rbind".to_string(), "ro".to_string()],
+            uid_mappings: None,
+            gid_mappings: None,
+        };
+        let result = in_mount_namespace(|| {
+            let m = Mount::from(&spec_mount);
+            let Ok(bind_source) = open_tree(AT_FDCWD, &source, true) else {
+                return false;
+            };
+            attach_bind(&bind_source, &m).is_ok()
+                && fs::write(destination.join("file"), "").is_err_and(|e| e.kind() == io::ErrorKind::ReadOnlyFilesystem)
+                && fs::write(source.join("file"), "").is_ok()
+        });
+
+        let _ = fs::remove_dir_all(&dir);
+        assert_ne!(result, Some(false));
+    }
 }
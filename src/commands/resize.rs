@@ -0,0 +1,20 @@
+use crate::context::FrogContext;
+use crate::errors::ContainerError;
+use crate::shim;
+use crate::shim::Request;
+
+pub fn run(context: FrogContext, container_id: String, rows: u16, cols: u16) -> Result<(), ContainerError> {
+    {
+        let _lock = context.lookup_container(&container_id)?;
+    }
+
+    // only the shim holds the pty master, a container run attached is resized by `frogc run` itself
+    let response = shim::request(&context, &container_id, &Request::Resize { rows, cols })
+        .map_err(ContainerError::wrap)?
+        .ok_or(ContainerError::InvalidState(
+            "Container is not running under a shim".to_string(),
+        ))?;
+    response.into_result()?;
+
+    Ok(())
+}
@@ -0,0 +1,28 @@
+use crate::cgroup::Cgroup;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+
+pub fn run(context: FrogContext, container_id: String) -> Result<(), ContainerError> {
+    let _lock = context.lookup_container(&container_id)?;
+
+    let mut state = context
+        .read_state(&container_id)
+        .map_err(ContainerError::wrap)?;
+    if state.status != "paused" {
+        return Err(ContainerError::InvalidState(
+            "Container is not paused".to_string(),
+        ));
+    }
+
+    Cgroup::new(&container_id)
+        .thaw()
+        .map_err(WrapError::wrapper("thawing cgroup"))
+        .map_err(ContainerError::wrap)?;
+
+    state.status = "running".to_string();
+    context
+        .write_state(&container_id, state)
+        .map_err(ContainerError::wrap)?;
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use crate::commands::{create, start};
+use crate::console;
+use crate::console::RawTerminal;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::reexec::Stdio;
+use crate::shim;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::signal::{SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::unistd::{dup2_stdin, isatty};
+use std::fs::{self, File};
+use std::io;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, OwnedFd};
+use std::os::unix::net::UnixListener;
+
+// creates and starts a container and stays attached to it until it exits
+// returns the exit code of the container
+pub fn run(
+    context: FrogContext,
+    spec_path: String,
+    container_id: String,
+    interactive: bool,
+    tty: bool,
+) -> Result<i32, ContainerError> {
+    let mut spec = create::read_spec(&spec_path)?;
+    if tty {
+        spec.terminal = Some(true);
+    }
+    let terminal = spec.terminal.unwrap_or(false);
+
+    // with a terminal the container sends us the pty master over a console socket in its run dir
+    let console_socket_path = shim::console_socket_path(&context, &container_id);
+    let console_socket = terminal.then(|| console_socket_path.to_string_lossy().to_string());
+
+    create::create(&context, &container_id, spec, console_socket, None)?;
+
+    let listener = if terminal {
+        Some(
+            UnixListener::bind(&console_socket_path)
+                .map_err(WrapError::wrapper("binding console socket"))
+                .map_err(ContainerError::wrap)?,
+        )
+    } else {
+        None
+    };
+
+    // without a terminal the container inherits our stdio, so we only have to take stdin away if it should not read it
+    if !terminal && !interactive {
+        let dev_null = File::open("/dev/null").map_err(ContainerError::wrap)?;
+        dup2_stdin(&dev_null).map_err(ContainerError::wrap)?;
+    }
+
+    let child_pid = start::spawn(&context, &container_id, Stdio::default())?;
+
+    if let Some(listener) = listener {
+        let master = console::accept(&listener, child_pid)
+            .map_err(WrapError::wrapper("receiving pty master"))
+            .map_err(ContainerError::wrap)?;
+        drop(listener);
+        let _ = fs::remove_file(&console_socket_path);
+
+        if let Some(master) = master {
+            proxy(master, interactive)
+                .map_err(WrapError::wrapper("proxying terminal"))
+                .map_err(ContainerError::wrap)?;
+        }
+    }
+
+    let status = start::wait(&context, &container_id, child_pid)?;
+
+    Ok(start::exit_code(status))
+}
+
+// copies between our stdio and the pty master until the container side of the pty is closed
+// window size changes of our terminal are forwarded to the pty
+fn proxy(master: OwnedFd, interactive: bool) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut master = File::from(master);
+
+    let is_tty = isatty(stdin.as_fd()).unwrap_or(false);
+    let _raw_terminal = if interactive && is_tty {
+        Some(RawTerminal::enable()?)
+    } else {
+        None
+    };
+
+    if let Ok(size) = console::window_size(stdin.as_fd()) {
+        console::set_window_size(master.as_fd(), &size)?;
+    }
+
+    let mut mask = SigSet::empty();
+    mask.add(Signal::SIGWINCH);
+    mask.thread_block()?;
+    let sigwinch = SignalFd::with_flags(&mask, SfdFlags::SFD_CLOEXEC)?;
+
+    let mut stdin_open = interactive;
+    let mut buf = [0u8; 4096];
+
+    loop {
+        let mut fds = vec![
+            PollFd::new(master.as_fd(), PollFlags::POLLIN),
+            PollFd::new(sigwinch.as_fd(), PollFlags::POLLIN),
+        ];
+        if stdin_open {
+            fds.push(PollFd::new(stdin.as_fd(), PollFlags::POLLIN));
+        }
+
+        match poll(&mut fds, PollTimeout::NONE) {
+            Err(Errno::EINTR) => continue,
+            r => r?,
+        };
+
+        let master_ready = fds[0].any().unwrap_or(false);
+        let resized = fds[1].any().unwrap_or(false);
+        let stdin_ready = stdin_open && fds[2].any().unwrap_or(false);
+
+        if master_ready {
+            // reading the master fails with EIO once every process in the container closed the slave
+            match master.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => {
+                    stdout.write_all(&buf[..n])?;
+                    stdout.flush()?;
+                }
+                Err(e) if e.raw_os_error() == Some(Errno::EIO as i32) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        if resized {
+            sigwinch.read_signal()?;
+            if let Ok(size) = console::window_size(stdin.as_fd()) {
+                console::set_window_size(master.as_fd(), &size)?;
+            }
+        }
+
+        if stdin_ready {
+            match stdin.lock().read(&mut buf)? {
+                0 => stdin_open = false,
+                n => master.write_all(&buf[..n])?,
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::capabilities;
+use crate::devices;
+use crate::errors::{ContainerError, WrapError};
+use crate::namespaces::DEFAULT_NAMESPACES;
+use crate::paths;
+use crate::spec::{ContainerSpec, LinuxIdMapping, Mount, Namespace, Rootfs, User};
+use nix::unistd::{getegid, geteuid};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::{env, io};
+
+const DEFAULT_ENV: [&str; 2] = [
+    "PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+    "TERM=xterm",
+];
+
+// writes a default spec to stdout or to a new file
+// with rootless the container gets a user namespace mapping root to the calling user
+pub fn run(output: Option<String>, rootfs: Option<String>, rootless: bool) -> Result<(), ContainerError> {
+    let rootfs = match rootfs {
+        Some(rootfs) => rootfs,
+        None => env::current_dir()
+            .map_err(ContainerError::wrap)?
+            .join("rootfs")
+            .to_string_lossy()
+            .to_string(),
+    };
+
+    write(&default_spec(rootfs, rootless), output)
+}
+
+// writes a spec to stdout or to a new file
+pub fn write(spec: &ContainerSpec, output: Option<String>) -> Result<(), ContainerError> {
+    let content = serde_json::to_string_pretty(spec).map_err(ContainerError::wrap)?;
+
+    match output {
+        // an existing spec is not overwritten, it may have been edited by hand
+        Some(path) => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .map_err(WrapError::wrapper(format!("creating {}", path).as_str()))
+                .map_err(ContainerError::wrap)?;
+            writeln!(file, "{}", content).map_err(ContainerError::wrap)
+        }
+        None => writeln!(io::stdout(), "{}", content).map_err(ContainerError::wrap),
+    }
+}
+
+pub fn default_spec(rootfs: String, rootless: bool) -> ContainerSpec {
+    let mut namespaces: Vec<Namespace> = DEFAULT_NAMESPACES
+        .iter()
+        .map(|ns_type| Namespace {
+            ns_type: ns_type.to_string(),
+            path: None,
+        })
+        .collect();
+
+    let (uid_mappings, gid_mappings) = if rootless {
+        namespaces.push(Namespace {
+            ns_type: "user".to_string(),
+            path: None,
+        });
+        (
+            Some(vec![id_mapping(geteuid().as_raw())]),
+            Some(vec![id_mapping(getegid().as_raw())]),
+        )
+    } else {
+        (None, None)
+    };
+
+    ContainerSpec {
+        rootfs: Rootfs::Path(rootfs),
+        rootfs_readonly: None,
+        work_dir: Some("/".to_string()),
+        cmd: vec!["sh".to_string()],
+        env: Some(DEFAULT_ENV.iter().map(|var| var.to_string()).collect()),
+        user: Some(User {
+            uid: 0,
+            gid: 0,
+            additional_gids: None,
+        }),
+        capabilities: Some(capabilities::default_capabilities()),
+        hostname: None,
+        domainname: None,
+        terminal: Some(true),
+        init: None,
+        cpu: None,
+        memory: None,
+        cpu_quota: None,
+        cpu_period: None,
+        pids: None,
+        namespaces: Some(namespaces),
+        uid_mappings,
+        gid_mappings,
+        mounts: default_mounts(rootless),
+        devices: Some(devices::default_devices()),
+        device_rules: None,
+        masked_paths: Some(paths::default_masked_paths()),
+        readonly_paths: Some(paths::default_readonly_paths()),
+        sysctl: None,
+        dns: None,
+        extra_hosts: None,
+        log: None,
+        annotations: None,
+        hooks: None,
+    }
+}
+
+// the only id mapped into the container is the caller's, as root
+fn id_mapping(host_id: u32) -> LinuxIdMapping {
+    LinuxIdMapping {
+        container_id: 0,
+        host_id,
+        size: 1,
+    }
+}
+
+fn default_mounts(rootless: bool) -> Vec<Mount> {
+    let mount = |destination: &str, fs_type: &str, source: &str, options: &[&str]| Mount {
+        destination: destination.to_string(),
+        fs_type: fs_type.to_string(),
+        source: source.to_string(),
+        rbind: None,
+        options: options.iter().map(|o| o.to_string()).collect(),
+        uid_mappings: None,
+        gid_mappings: None,
+    };
+
+    // gid 5 is the tty group, which is not mapped into a rootless container
+    let devpts_options: &[&str] = if rootless {
+        &["nosuid", "noexec", "newinstance", "ptmxmode=0666", "mode=0620"]
+    } else {
+        &["nosuid", "noexec", "newinstance", "ptmxmode=0666", "mode=0620", "gid=5"]
+    };
+
+    // sysfs can only be mounted by the owner of the network namespace, a rootless container borrows the host's
+    let sys = if rootless {
+        mount("/sys", "none", "/sys", &["rbind", "nosuid", "noexec", "nodev", "ro"])
+    } else {
+        mount("/sys", "sysfs", "sysfs", &["nosuid", "noexec", "nodev", "ro"])
+    };
+
+    vec![
+        mount("/proc", "proc", "proc", &["nosuid", "noexec", "nodev"]),
+        mount("/dev", "tmpfs", "tmpfs", &["nosuid", "strictatime", "mode=755", "size=65536k"]),
+        mount("/dev/pts", "devpts", "devpts", devpts_options),
+        mount("/dev/shm", "tmpfs", "shm", &["nosuid", "noexec", "nodev", "mode=1777", "size=65536k"]),
+        mount("/dev/mqueue", "mqueue", "mqueue", &["nosuid", "noexec", "nodev"]),
+        sys,
+    ]
+}
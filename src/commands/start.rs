@@ -1,18 +1,149 @@
+use crate::cgroup::Cgroup;
 use crate::cli::ReExecCommands;
-use crate::context::FrogContext;
-use crate::errors::ContainerError;
+use crate::commands::kill;
+use crate::context::{process_start_time, FrogContext};
+use crate::etc;
+use crate::errors::{ContainerError, WrapError};
+use crate::hooks;
+use crate::namespaces;
+use crate::oci;
 use crate::reexec;
+use crate::shim;
+use crate::shim::Shim;
+use crate::spec::ContainerState;
+use crate::stats;
+use crate::reexec::Stdio;
+use crate::syscall::pidfd_open;
+use chrono::Utc;
+use nix::errno::Errno;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::signal::{self, Signal};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::Pid;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::os::fd::AsFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::{fs, io};
 
-pub fn run(context: FrogContext, container_id: String) -> Result<(), ContainerError> {
-    let (exists, _lock) = context
-        .lock_container(&container_id)
+const EXEC_FIFO: &str = "exec.fifo";
+
+// the fifo the process of a container created from a bundle waits on until it is started
+pub fn exec_fifo_path(context: &FrogContext, container_id: &str) -> PathBuf {
+    context.container_run_dir(container_id).join(EXEC_FIFO)
+}
+
+// returns the exit code of the container when running in the foreground, 0 otherwise
+pub fn run(context: FrogContext, container_id: String, foreground: bool) -> Result<i32, ContainerError> {
+    // check what we can up front, so the common errors are reported with their proper exit codes
+    let state = {
+        let _lock = context.lookup_container(&container_id)?;
+
+        context
+            .read_state(&container_id)
+            .map_err(ContainerError::wrap)?
+    };
+
+    if state.bundle.is_some() {
+        if foreground {
+            return Err(ContainerError::InvalidState(
+                "--foreground is not supported for containers created from a bundle".to_string(),
+            ));
+        }
+
+        start_created(&context, &container_id)?;
+        return Ok(0);
+    }
+
+    if state.status != "stopped" {
+        return Err(ContainerError::InvalidState(
+            "Container is not stopped".to_string(),
+        ));
+    }
+
+    if foreground {
+        let status = Shim::spawn(&context, &container_id, true)?.run()?;
+        return Ok(exit_code(status));
+    }
+
+    shim::start_detached(&context, &container_id)?;
+
+    Ok(0)
+}
+
+// lets the process of a container created from a bundle run its command
+// opening the exec fifo unblocks the process, it writes a byte to the fifo right before executing the command
+fn start_created(context: &FrogContext, container_id: &str) -> Result<(), ContainerError> {
+    let lock = context.lookup_container(container_id)?;
+    let mut state = context
+        .read_state(container_id)
         .map_err(ContainerError::wrap)?;
-    if !exists {
-        return Err(ContainerError::NotFound);
+
+    let pid = match (state.status.as_str(), state.pid) {
+        ("created", Some(pid)) => Pid::from_raw(pid as i32),
+        ("stopped", _) => {
+            return Err(ContainerError::InvalidState(
+                "Container has stopped, containers created from a bundle cannot be started again".to_string(),
+            ));
+        }
+        _ => {
+            return Err(ContainerError::InvalidState(
+                "Container is not created".to_string(),
+            ));
+        }
+    };
+
+    // the pidfd tells us if the process dies instead of writing to the fifo
+    let pidfd = pidfd_open(pid).map_err(ContainerError::wrap)?;
+    let fifo_path = exec_fifo_path(context, container_id);
+    let mut fifo = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+        .open(&fifo_path)
+        .map_err(WrapError::wrapper("opening exec fifo"))
+        .map_err(ContainerError::wrap)?;
+
+    let mut fds = [
+        PollFd::new(fifo.as_fd(), PollFlags::POLLIN),
+        PollFd::new(pidfd.as_fd(), PollFlags::POLLIN),
+    ];
+    loop {
+        match poll(&mut fds, PollTimeout::NONE) {
+            Err(Errno::EINTR) => continue,
+            r => r.map_err(ContainerError::wrap)?,
+        };
+        break;
     }
 
+    if !fds[0].any().unwrap_or(false) {
+        return Err(ContainerError::wrap(io::Error::other(
+            "container process exited before it was started",
+        )));
+    }
+
+    fifo.read_exact(&mut [0u8])
+        .map_err(WrapError::wrapper("reading exec fifo"))
+        .map_err(ContainerError::wrap)?;
+    let _ = fs::remove_file(&fifo_path);
+
+    state.status = "running".to_string();
+    context
+        .write_state(container_id, state.clone())
+        .map_err(ContainerError::wrap)?;
+    drop(lock);
+
+    run_poststart(&state)
+}
+
+// starts the container process and marks the container as running
+// the process of a container created from a bundle is only set up, it waits in the created state until started
+pub fn spawn(context: &FrogContext, container_id: &str, stdio: Stdio) -> Result<Pid, ContainerError> {
+    let lock = context.lookup_container(container_id)?;
+
     let mut state = context
-        .read_state(&container_id)
+        .read_state(container_id)
         .map_err(ContainerError::wrap)?;
     if state.status != "stopped" {
         return Err(ContainerError::InvalidState(
@@ -20,18 +151,164 @@ pub fn run(context: FrogContext, container_id: String) -> Result<(), ContainerEr
         ));
     }
 
-    let child_pid = reexec::run(ReExecCommands::Start {
-        container_id: container_id.clone(),
-    }).map_err(ContainerError::wrap)?;
+    // for a bundle config.json is authoritative, like runc we leave its /etc alone
+    if state.bundle.is_none() {
+        etc::write(context, container_id, &state.spec)
+            .map_err(WrapError::wrapper("generating /etc files"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    let cgroup = if Cgroup::is_supported() {
+        let cgroup = Cgroup::new(container_id);
+        cgroup
+            .create()
+            .map_err(WrapError::wrapper("creating cgroup"))
+            .map_err(ContainerError::wrap)?;
+        cgroup
+            .apply_resources(&state.spec)
+            .map_err(WrapError::wrapper("applying cgroup resources"))
+            .map_err(ContainerError::wrap)?;
+        Some(cgroup)
+    } else {
+        log::warn!("cgroup v2 is not available, starting container without a cgroup");
+        None
+    };
 
-    nix::sys::wait::waitpid(child_pid, None).map_err(ContainerError::wrap)?;
+    let flags = namespaces::clone_flags(&state.spec).map_err(ContainerError::wrap)?;
+    let child = reexec::run(context, ReExecCommands::Start {
+        sync_fd: stdio.sync.is_some().then_some(reexec::SYNC_FD),
+        container_id: container_id.to_string(),
+    }, flags, stdio).map_err(ContainerError::wrap)?;
+    let child_pid = child.pid;
 
     state.pid = Some(child_pid.as_raw() as u32);
+    state.pid_start_time = process_start_time(child_pid.as_raw() as u32);
+    if let Err(e) = prepare_child(&state, child_pid, cgroup.as_ref()) {
+        abort_child(child_pid, cgroup.as_ref());
+        return Err(e);
+    }
 
-    state.status = "running".to_string();
+    // the state has to be written before resuming, the child reads it right after the re-exec
+    state.status = if state.bundle.is_some() { "created" } else { "running" }.to_string();
+    state.exit_code = None;
+    state.exit_signal = None;
+    state.oom_killed = None;
+    state.finished_at = None;
+    context
+        .write_state(container_id, state.clone())
+        .map_err(ContainerError::wrap)?;
+
+    child.resume().map_err(ContainerError::wrap)?;
+
+    // without the created state, a container counts as started as soon as its process runs
+    if state.bundle.is_none() {
+        drop(lock);
+        if let Err(e) = run_poststart(&state) {
+            let status = waitpid(child_pid, None).map_err(ContainerError::wrap)?;
+            record_exit(context, container_id, status)?;
+            return Err(e);
+        }
+    }
+
+    Ok(child_pid)
+}
+
+// everything between cloning the child and resuming it, the child has its namespaces but nothing is set up inside yet
+fn prepare_child(state: &ContainerState, child_pid: Pid, cgroup: Option<&Cgroup>) -> Result<(), ContainerError> {
+    namespaces::write_id_mappings(child_pid, &state.spec)
+        .map_err(WrapError::wrapper("writing user namespace id mappings"))
+        .map_err(ContainerError::wrap)?;
+
+    // the child waits for us to move it into the cgroup, so every process it spawns is accounted for
+    if let Some(cgroup) = cgroup {
+        cgroup
+            .add_process(child_pid)
+            .map_err(WrapError::wrapper("adding container to cgroup"))
+            .map_err(ContainerError::wrap)?;
+    }
+
+    let hook_state = oci::State::new(state).with_status("creating");
+    for hook_type in ["prestart", "createRuntime"] {
+        hooks::run(&state.spec, hook_type, &hook_state).map_err(ContainerError::wrap)?;
+    }
+
+    Ok(())
+}
+
+// the child never got to run, so nothing of it may stay behind: it is killed and reaped, and its cgroup
+// with the device filter is removed, the state on disk still says stopped
+fn abort_child(child_pid: Pid, cgroup: Option<&Cgroup>) {
+    let _ = signal::kill(child_pid, Signal::SIGKILL);
+    let _ = waitpid(child_pid, None);
+    if let Some(cgroup) = cgroup
+        && let Err(e) = cgroup.remove()
+    {
+        log::warn!("removing cgroup of {}: {}", child_pid, e);
+    }
+}
+
+// the poststart hooks run on the host, like runc we kill the container if one of them fails
+fn run_poststart(state: &ContainerState) -> Result<(), ContainerError> {
+    if let Err(e) = hooks::run(&state.spec, "poststart", &oci::State::new(state)) {
+        kill::signal_all(&state.id, state.pid, Signal::SIGKILL)?;
+        return Err(ContainerError::wrap(e));
+    }
+
+    Ok(())
+}
+
+// waits for the container process to exit and marks the container as stopped
+// the lock is not held while waiting so other commands (e.g. pause) can operate on the running container
+pub fn wait(context: &FrogContext, container_id: &str, child_pid: Pid) -> Result<WaitStatus, ContainerError> {
+    let status = waitpid(child_pid, None).map_err(ContainerError::wrap)?;
+    record_exit(context, container_id, status)?;
+
+    Ok(status)
+}
+
+// marks the container as stopped with the exit status of its process and removes its cgroup
+pub fn record_exit(context: &FrogContext, container_id: &str, status: WaitStatus) -> Result<(), ContainerError> {
+    let _lock = context.lookup_container(container_id)?;
+    let mut state = context
+        .read_state(container_id)
+        .map_err(ContainerError::wrap)?;
+
+    // the OOM kill counter is gone with the cgroup, so it has to be read before removing it
+    let cgroup = Cgroup::is_supported().then(|| Cgroup::new(container_id));
+    let oom_killed = cgroup
+        .as_ref()
+        .and_then(|cgroup| stats::oom_kills(cgroup).ok())
+        .map(|kills| kills > 0);
+
+    state.pid = None;
+    state.pid_start_time = None;
+    state.status = "stopped".to_string();
+    state.exit_code = Some(exit_code(status));
+    state.exit_signal = match status {
+        WaitStatus::Signaled(_, signal, _) => Some(signal.as_str().to_string()),
+        _ => None,
+    };
+    state.oom_killed = oom_killed;
+    state.finished_at = Some(Utc::now());
     context
-        .write_state(&container_id, state)
+        .write_state(container_id, state)
         .map_err(ContainerError::wrap)?;
 
+    if let Some(cgroup) = cgroup {
+        cgroup
+            .remove()
+            .map_err(WrapError::wrapper("removing cgroup"))
+            .map_err(ContainerError::wrap)?;
+    }
+
     Ok(())
 }
+
+// maps a wait status to a shell style exit code, processes killed by a signal exit with 128 + signal
+pub fn exit_code(status: WaitStatus) -> i32 {
+    match status {
+        WaitStatus::Exited(_, code) => code,
+        WaitStatus::Signaled(_, signal, _) => 128 + signal as i32,
+        _ => 0,
+    }
+}
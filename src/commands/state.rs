@@ -0,0 +1,40 @@
+use crate::context::FrogContext;
+use crate::errors::ContainerError;
+use crate::shim;
+use crate::oci;
+use crate::shim::Request;
+
+pub fn run(context: FrogContext, container_id: String) -> Result<(), ContainerError> {
+    // the lock is released before asking the shim, it takes the lock itself to read the state
+    {
+        let _lock = context.lookup_container(&container_id)?;
+    }
+
+    let from_shim = match shim::request(&context, &container_id, &Request::State) {
+        Ok(Some(response)) => response.into_result()?.state,
+        Ok(None) => None,
+        Err(e) => {
+            log::warn!("could not query shim, reading state file: {}", e);
+            None
+        }
+    };
+
+    let state = match from_shim {
+        Some(state) => state,
+        None => {
+            let _lock = context.lookup_container(&container_id)?;
+            context
+                .read_state(&container_id)
+                .map_err(ContainerError::wrap)?
+        }
+    };
+
+    // containers created from a bundle are driven by tools written for runc, which expect the OCI state
+    let output = match &state.bundle {
+        Some(_) => serde_json::to_string_pretty(&oci::State::new(&state)),
+        None => serde_json::to_string_pretty(&state),
+    };
+    println!("{}", output.map_err(ContainerError::wrap)?);
+
+    Ok(())
+}
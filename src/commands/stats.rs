@@ -0,0 +1,28 @@
+use crate::cgroup::Cgroup;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::stats;
+use crate::stats::Event;
+
+pub fn run(context: FrogContext, container_id: String) -> Result<(), ContainerError> {
+    let _lock = context.lookup_container(&container_id)?;
+
+    let state = context
+        .read_state(&container_id)
+        .map_err(ContainerError::wrap)?;
+    if state.status != "running" && state.status != "paused" {
+        return Err(ContainerError::InvalidState(
+            "Container is not running".to_string(),
+        ));
+    }
+
+    let stats = stats::collect(&Cgroup::new(&container_id))
+        .map_err(WrapError::wrapper("reading cgroup stats"))
+        .map_err(ContainerError::wrap)?;
+
+    Event::new("stats", &container_id, Some(stats))
+        .print()
+        .map_err(ContainerError::wrap)?;
+
+    Ok(())
+}
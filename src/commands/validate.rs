@@ -0,0 +1,23 @@
+use crate::commands::create;
+use crate::errors::{ContainerError, WrapError};
+use crate::oci;
+use crate::validate;
+use std::fs;
+
+pub fn run(spec_path: Option<String>, bundle: Option<String>) -> Result<(), ContainerError> {
+    let spec = match bundle {
+        Some(bundle) => {
+            let bundle = fs::canonicalize(bundle)
+                .map_err(WrapError::wrapper("resolving bundle path"))
+                .map_err(ContainerError::wrap)?;
+            oci::load_bundle(&bundle)?
+        }
+        // clap makes sure one of them is given
+        None => create::read_spec(spec_path.as_deref().unwrap_or("-"))?,
+    };
+
+    validate::validate(&spec)?;
+    println!("spec is valid");
+
+    Ok(())
+}
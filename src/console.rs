@@ -0,0 +1,208 @@
+use crate::syscall::pidfd_open;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::libc;
+use nix::mount::{mount, MsFlags};
+use nix::pty::{grantpt, posix_openpt, ptsname_r, unlockpt, PtyMaster};
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg, Termios};
+use nix::sys::socket::{recvmsg, sendmsg, ControlMessage, ControlMessageOwned, MsgFlags};
+use nix::unistd::{dup2_stdin, dup2_stdout, dup2_stderr, setsid, Pid};
+use std::fs::{File, OpenOptions};
+use std::io::{IoSlice, IoSliceMut};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::{symlink, OpenOptionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::{fs, io};
+
+// sets up the pty for the container process, must be called inside the container after the devices are created
+// and before the rootfs is made read-only
+// the master is sent over the console socket, the slave becomes the controlling terminal and stdio of the calling process
+pub fn setup(console_socket: &UnixStream) -> io::Result<()> {
+    ensure_devpts()?;
+    let (master, slave_path) = open_pty()?;
+
+    // /dev/console is expected to be the container's terminal, we bind mount the slave over it
+    if !Path::new("/dev/console").exists() {
+        File::create("/dev/console")?;
+    }
+    mount(
+        Some(slave_path.as_str()),
+        "/dev/console",
+        None::<&str>,
+        MsFlags::MS_BIND,
+        None::<&str>,
+    )?;
+
+    attach(console_socket, master, &slave_path)
+}
+
+// like setup, but for a process executed in a running container, whose /dev/console belongs to the container process
+pub fn setup_exec(console_socket: &UnixStream) -> io::Result<()> {
+    let (master, slave_path) = open_pty()?;
+    attach(console_socket, master, &slave_path)
+}
+
+fn open_pty() -> io::Result<(PtyMaster, String)> {
+    let master = posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY | OFlag::O_CLOEXEC)?;
+    grantpt(&master)?;
+    unlockpt(&master)?;
+    let slave_path = ptsname_r(&master)?;
+
+    Ok((master, slave_path))
+}
+
+fn attach(console_socket: &UnixStream, master: PtyMaster, slave_path: &str) -> io::Result<()> {
+    send_master(console_socket, &master)?;
+    drop(master);
+
+    // a new session is needed to acquire a controlling terminal
+    setsid()?;
+
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open(slave_path)?;
+
+    if unsafe { libc::ioctl(slave.as_raw_fd(), libc::TIOCSCTTY, 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    dup2_stdin(&slave)?;
+    dup2_stdout(&slave)?;
+    dup2_stderr(&slave)?;
+
+    Ok(())
+}
+
+// the pty master is passed to whoever listens on the console socket as SCM_RIGHTS ancillary data
+// this is the same protocol runc uses, so tools written for runc's --console-socket work unchanged
+fn send_master(console_socket: &UnixStream, master: &PtyMaster) -> io::Result<()> {
+    let fds = [master.as_raw_fd()];
+    let name = b"/dev/ptmx";
+    sendmsg::<()>(
+        console_socket.as_raw_fd(),
+        &[IoSlice::new(name)],
+        &[ControlMessage::ScmRights(&fds)],
+        MsgFlags::empty(),
+        None,
+    )?;
+
+    Ok(())
+}
+
+// waits for the container to connect to the console socket and send its pty master
+// returns None if the container exits before it connects, e.g. because its setup failed
+pub fn accept(listener: &UnixListener, child_pid: Pid) -> io::Result<Option<OwnedFd>> {
+    let pidfd = pidfd_open(child_pid)?;
+
+    let mut fds = [
+        PollFd::new(listener.as_fd(), PollFlags::POLLIN),
+        PollFd::new(pidfd.as_fd(), PollFlags::POLLIN),
+    ];
+    // a signal such as SIGCHLD or SIGWINCH must not abort the setup
+    while let Err(e) = poll(&mut fds, PollTimeout::NONE) {
+        if e != Errno::EINTR {
+            return Err(e.into());
+        }
+    }
+
+    if !fds[0].any().unwrap_or(false) {
+        return Ok(None);
+    }
+
+    let (stream, _) = listener.accept()?;
+    Ok(Some(receive_master(&stream)?))
+}
+
+fn receive_master(console_socket: &UnixStream) -> io::Result<OwnedFd> {
+    let mut buf = [0u8; 4096];
+    let mut iov = [IoSliceMut::new(&mut buf)];
+    let mut cmsg_buf = nix::cmsg_space!(RawFd);
+
+    let msg = recvmsg::<()>(
+        console_socket.as_raw_fd(),
+        &mut iov,
+        Some(&mut cmsg_buf),
+        MsgFlags::MSG_CMSG_CLOEXEC,
+    )?;
+
+    for cmsg in msg.cmsgs()? {
+        if let ControlMessageOwned::ScmRights(fds) = cmsg
+            && let Some(fd) = fds.first()
+        {
+            return Ok(unsafe { OwnedFd::from_raw_fd(*fd) });
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "console socket did not receive a file descriptor",
+    ))
+}
+
+// puts the terminal into raw mode so every key press is passed through to the container's pty
+// the original settings are restored when the guard is dropped
+pub struct RawTerminal {
+    original: Termios,
+}
+
+impl RawTerminal {
+    pub fn enable() -> io::Result<Self> {
+        let original = tcgetattr(io::stdin())?;
+        let mut raw = original.clone();
+        cfmakeraw(&mut raw);
+        tcsetattr(io::stdin(), SetArg::TCSANOW, &raw)?;
+
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if let Err(e) = tcsetattr(io::stdin(), SetArg::TCSANOW, &self.original) {
+            log::warn!("could not restore terminal settings: {}", e);
+        }
+    }
+}
+
+pub fn window_size(fd: BorrowedFd) -> io::Result<libc::winsize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(fd.as_raw_fd(), libc::TIOCGWINSZ, &mut size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(size)
+}
+
+pub fn set_window_size(fd: BorrowedFd, size: &libc::winsize) -> io::Result<()> {
+    if unsafe { libc::ioctl(fd.as_raw_fd(), libc::TIOCSWINSZ, size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// mounts a private devpts instance unless the spec already provides one
+// newinstance makes sure the container cannot see the host's ptys, ptmxmode makes the ptmx node usable by everyone
+fn ensure_devpts() -> io::Result<()> {
+    if !Path::new("/dev/pts/ptmx").exists() {
+        fs::create_dir_all("/dev/pts")?;
+        mount(
+            Some("devpts"),
+            "/dev/pts",
+            Some("devpts"),
+            MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC,
+            Some("newinstance,ptmxmode=0666,mode=0620"),
+        )?;
+    }
+
+    // posix_openpt opens /dev/ptmx, which has to refer to the ptmx of our devpts instance
+    if fs::symlink_metadata("/dev/ptmx").is_err() {
+        symlink("pts/ptmx", "/dev/ptmx")?;
+    }
+
+    Ok(())
+}
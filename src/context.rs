@@ -3,24 +3,61 @@ use std::{fs, io};
 use std::io::Read;
 use std::path::PathBuf;
 use fs2::FileExt;
+use crate::cli::LogFormat;
+use crate::errors::ContainerError;
 use crate::spec;
 
 #[derive(Clone)]
 pub struct FrogContext {
     pub run_dir_base: PathBuf,
+    // the global logging flags, the re-exec'd process gets the same ones
+    pub log: Option<String>,
+    pub log_format: LogFormat,
+    pub debug: bool,
 }
 
 impl FrogContext {
     pub fn new(run_dir_base: impl Into<PathBuf>) -> Self {
         Self {
             run_dir_base: run_dir_base.into(),
+            log: None,
+            log_format: LogFormat::Text,
+            debug: false,
         }
     }
 
+    pub fn with_logging(mut self, log: Option<String>, log_format: LogFormat, debug: bool) -> Self {
+        self.log = log;
+        self.log_format = log_format;
+        self.debug = debug;
+        self
+    }
+
     pub fn container_run_dir(&self, container_id: &str) -> PathBuf {
         self.run_dir_base.join(container_id)
     }
 
+    // returns the ids of all containers that have a state in the run dir
+    pub fn list_containers(&self) -> io::Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.run_dir_base) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut ids = vec![];
+        for entry in entries {
+            let entry = entry?;
+            if entry.path().join("state.json").exists() {
+                ids.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        ids.sort();
+        Ok(ids)
+    }
+
+    // only for create, the run dir of an unknown id is made, everything else looks the container up
     pub fn lock_container(&self, container_id: &str) -> io::Result<(bool, File)> {
         let run_dir = self.container_run_dir(container_id);
 
@@ -36,6 +73,23 @@ impl FrogContext {
         Ok((exists, lock_file))
     }
 
+    // locks an existing container without leaving anything behind for an unknown id, which runtime callers probe for
+    // a container whose create failed while we waited for the lock is gone as well, its state was never written
+    pub fn lookup_container(&self, container_id: &str) -> Result<File, ContainerError> {
+        let lock_file = match File::open(self.container_run_dir(container_id).join("lock")) {
+            Ok(lock_file) => lock_file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(ContainerError::NotFound),
+            Err(e) => return Err(ContainerError::wrap(e)),
+        };
+        lock_file.lock_exclusive().map_err(ContainerError::wrap)?;
+
+        if !self.state_file_path(container_id).exists() {
+            return Err(ContainerError::NotFound);
+        }
+
+        Ok(lock_file)
+    }
+
     pub fn state_file_path(&self, container_id: &str) -> PathBuf {
         self.container_run_dir(container_id).join("state.json")
     }
@@ -53,6 +107,45 @@ impl FrogContext {
         let mut file = File::open(path)?;
         let mut buffer = String::new();
         file.read_to_string(&mut buffer)?;
-        Ok(serde_json::from_str(&buffer)?)
+        let mut state: spec::ContainerState = serde_json::from_str(&buffer)?;
+
+        // no shim records the exit of containers created from a bundle, their process is reaped by whoever created them
+        if state.bundle.is_some()
+            && state.status != "stopped"
+            && !state.pid.is_some_and(|pid| process_alive(pid, state.pid_start_time))
+        {
+            state.status = "stopped".to_string();
+            state.pid = None;
+            state.pid_start_time = None;
+        }
+
+        Ok(state)
     }
 }
+
+// the start time is field 22 of /proc/<pid>/stat, in clock ticks after boot
+pub fn process_start_time(pid: u32) -> Option<u64> {
+    stat_fields(pid)?.get(19)?.parse().ok()
+}
+
+// zombies count as gone, the process has exited and only waits for its parent to reap it
+// a process started at another time than the recorded one only reuses the pid, like runc we compare the two
+// states written before the start time was recorded only have the pid to go by
+fn process_alive(pid: u32, start_time: Option<u64>) -> bool {
+    let Some(fields) = stat_fields(pid) else {
+        return false;
+    };
+
+    if matches!(fields.first().map(String::as_str), Some("Z" | "X")) {
+        return false;
+    }
+    start_time.is_none_or(|start_time| fields.get(19).and_then(|f| f.parse().ok()) == Some(start_time))
+}
+
+// the fields after the command name, which is in parentheses and may contain anything
+// the first one is the process state, field 3 of the whole line
+fn stat_fields(pid: u32) -> Option<Vec<String>> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let (_, rest) = stat.rsplit_once(')')?;
+    Some(rest.split_whitespace().map(str::to_string).collect())
+}
@@ -0,0 +1,177 @@
+use crate::spec::{ContainerSpec, Device, DeviceRule};
+use crate::syscall::{move_mount, open_tree};
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::stat::{mknod, Mode, SFlag};
+use nix::unistd::{chown, Gid, Uid};
+use std::fs::{File, Permissions};
+use std::os::fd::{AsFd, BorrowedFd};
+use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::{fs, io};
+
+// the character devices every container gets unless its spec lists its own
+const DEFAULT_DEVICES: [(&str, u64, u64); 6] = [
+    ("/dev/null", 1, 3),
+    ("/dev/zero", 1, 5),
+    ("/dev/full", 1, 7),
+    ("/dev/random", 1, 8),
+    ("/dev/urandom", 1, 9),
+    ("/dev/tty", 5, 0),
+];
+const DEFAULT_FILE_MODE: u32 = 0o666;
+
+// created in every container unless something already exists at the path
+const SYMLINKS: [(&str, &str); 5] = [
+    ("/dev/ptmx", "pts/ptmx"),
+    ("/dev/fd", "/proc/self/fd"),
+    ("/dev/stdin", "/proc/self/fd/0"),
+    ("/dev/stdout", "/proc/self/fd/1"),
+    ("/dev/stderr", "/proc/self/fd/2"),
+];
+
+// devices every container may access, the console and pseudo terminals are not in the device list
+// creating nodes is allowed in general, what matters is whether they can be opened
+const ALLOWED_DEVICES: [(&str, Option<i64>, Option<i64>, &str); 5] = [
+    ("c", None, None, "m"),
+    ("b", None, None, "m"),
+    ("c", Some(5), Some(1), "rwm"),
+    ("c", Some(5), Some(2), "rwm"),
+    ("c", Some(136), None, "rwm"),
+];
+
+pub fn default_devices() -> Vec<Device> {
+    DEFAULT_DEVICES
+        .iter()
+        .map(|(path, major, minor)| Device {
+            path: path.to_string(),
+            dev_type: "c".to_string(),
+            major: Some(*major),
+            minor: Some(*minor),
+            file_mode: Some(DEFAULT_FILE_MODE),
+            uid: None,
+            gid: None,
+        })
+        .collect()
+}
+
+// the rules of the device cgroup: the spec's rules, then access to the container's own devices
+// like runc, the devices come last so a spec that starts by denying everything still gets them
+pub fn cgroup_rules(spec: &ContainerSpec) -> Vec<DeviceRule> {
+    let mut rules = spec.device_rules.clone().unwrap_or_default();
+
+    let allowed = ALLOWED_DEVICES.iter().map(|(dev_type, major, minor, access)| DeviceRule {
+        allow: true,
+        dev_type: Some(dev_type.to_string()),
+        major: *major,
+        minor: *minor,
+        access: Some(access.to_string()),
+    });
+    rules.extend(allowed);
+
+    let devices = spec.devices.clone().unwrap_or_else(default_devices);
+    // fifos are not subject to the device cgroup
+    for device in devices.iter().filter(|d| d.dev_type != "p") {
+        rules.push(DeviceRule {
+            allow: true,
+            dev_type: Some(if device.dev_type == "b" { "b" } else { "c" }.to_string()),
+            major: device.major.map(|major| major as i64),
+            minor: device.minor.map(|minor| minor as i64),
+            access: Some("rwm".to_string()),
+        });
+    }
+
+    rules
+}
+
+// creates the device nodes of the container, must be called after pivoting while the old root is still attached
+// nodes that already exist with the right type and number are kept, so a populated /dev in the rootfs is fine
+// where mknod is not permitted, e.g. in a user namespace, the host's node at the same path is bind mounted instead
+pub fn create(spec: &ContainerSpec, old_root: BorrowedFd) -> io::Result<()> {
+    let defaults;
+    let devices = match &spec.devices {
+        Some(devices) => devices,
+        None => {
+            defaults = default_devices();
+            &defaults
+        }
+    };
+
+    for device in devices {
+        create_device(device, old_root)
+            .map_err(|e| io::Error::other(format!("creating {}: {}", device.path, e)))?;
+    }
+
+    for (path, target) in SYMLINKS {
+        if fs::symlink_metadata(path).is_err() {
+            symlink(target, path)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn create_device(device: &Device, old_root: BorrowedFd) -> io::Result<()> {
+    let kind = match device.dev_type.as_str() {
+        "c" | "u" => SFlag::S_IFCHR,
+        "b" => SFlag::S_IFBLK,
+        "p" => SFlag::S_IFIFO,
+        t => return Err(io::Error::other(format!("unknown device type {}", t))),
+    };
+    let rdev = match (kind, device.major, device.minor) {
+        (SFlag::S_IFIFO, _, _) => 0,
+        (_, Some(major), Some(minor)) => libc::makedev(major as u32, minor as u32),
+        _ => return Err(io::Error::other("major and minor are required")),
+    };
+
+    let path = Path::new(&device.path);
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.mode() & SFlag::S_IFMT.bits() == kind.bits() && meta.rdev() == rdev => {
+            return set_mode_and_owner(path, device);
+        }
+        Ok(_) => fs::remove_file(path)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+        Err(e) => return Err(e),
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match mknod(path, kind, Mode::empty(), rdev) {
+        Ok(()) => (),
+        // the host's node belongs to the host, its mode and owner are not ours to change
+        Err(Errno::EPERM) if kind != SFlag::S_IFIFO => return bind_host_node(path, old_root),
+        Err(e) => return Err(e.into()),
+    }
+
+    set_mode_and_owner(path, device)
+}
+
+// mknod applies the umask and a node kept from the rootfs has whatever mode and owner the rootfs gave it
+// only what differs is changed, a rootfs node that already matches may belong to a user we cannot chown as
+fn set_mode_and_owner(path: &Path, device: &Device) -> io::Result<()> {
+    let meta = fs::symlink_metadata(path)?;
+    let mode = device.file_mode.unwrap_or(DEFAULT_FILE_MODE);
+    if meta.mode() & 0o7777 != mode {
+        fs::set_permissions(path, Permissions::from_mode(mode))?;
+    }
+
+    let uid = device.uid.filter(|&uid| uid != meta.uid());
+    let gid = device.gid.filter(|&gid| gid != meta.gid());
+    if uid.is_some() || gid.is_some() {
+        chown(path, uid.map(Uid::from_raw), gid.map(Gid::from_raw))?;
+    }
+
+    Ok(())
+}
+
+// the host's root is only reachable through the old root fd, so the node is cloned from there with open_tree
+fn bind_host_node(path: &Path, old_root: BorrowedFd) -> io::Result<()> {
+    File::create(path)?;
+
+    let relative = path.strip_prefix("/").unwrap_or(path);
+    let node = open_tree(old_root, relative, false)?;
+    move_mount(node.as_fd(), path)?;
+
+    Ok(())
+}
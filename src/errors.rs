@@ -28,6 +28,8 @@ pub enum ContainerError {
     AlreadyExists,
     NotFound,
     InvalidState(String),
+    // every problem found in a spec, prefixed with the JSON path of the field
+    InvalidSpec(Vec<String>),
     WrappedError(Box<dyn std::error::Error + Send + Sync>),
 }
 
@@ -41,8 +43,15 @@ impl std::fmt::Display for ContainerError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             ContainerError::AlreadyExists => write!(f, "Container already exists"),
-            ContainerError::NotFound => write!(f, "Container not found"),
+            ContainerError::NotFound => write!(f, "Container does not exist"),
             ContainerError::InvalidState(s) => write!(f, "Invalid state: {}", s),
+            ContainerError::InvalidSpec(problems) => {
+                write!(f, "Invalid spec:")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
             ContainerError::WrappedError(e) => write!(f, "{}", e),
         }
     }
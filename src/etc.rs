@@ -0,0 +1,148 @@
+use crate::context::FrogContext;
+use crate::namespaces;
+use crate::spec::ContainerSpec;
+use crate::syscall::{move_mount, open_tree};
+use nix::unistd::gethostname;
+use std::fs::{self, File};
+use std::net::IpAddr;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::path::{Path, PathBuf};
+use std::io;
+
+// the generated files live in the container run dir and are bind mounted over the rootfs' ones
+const ETC_DIR: &str = "etc";
+const FILES: [&str; 3] = ["hosts", "hostname", "resolv.conf"];
+
+const HOST_HOSTS: &str = "/etc/hosts";
+const HOST_RESOLV_CONF: &str = "/etc/resolv.conf";
+
+pub fn dir(context: &FrogContext, container_id: &str) -> PathBuf {
+    context.container_run_dir(container_id).join(ETC_DIR)
+}
+
+// generates the files on every start, so a restarted container sees the host's current resolvers
+pub fn write(context: &FrogContext, container_id: &str, spec: &ContainerSpec) -> io::Result<()> {
+    let dir = dir(context, container_id);
+    fs::create_dir_all(&dir)?;
+
+    let private_network = namespaces::is_private(spec, "network");
+    let hostname = if namespaces::is_private(spec, "uts") {
+        spec.hostname.clone().unwrap_or(container_id.to_string())
+    } else {
+        gethostname()?.to_string_lossy().to_string()
+    };
+
+    fs::write(dir.join("hosts"), hosts(spec, &hostname, private_network)?)?;
+    fs::write(dir.join("hostname"), format!("{}\n", hostname))?;
+    fs::write(dir.join("resolv.conf"), resolv_conf(spec, private_network)?)?;
+
+    Ok(())
+}
+
+// bind mounts the generated files over /etc, must be called after pivoting so symlinks in the rootfs resolve inside it
+// the run dir is only reachable through dir, which has to be opened before pivoting
+// a file the spec mounts itself is left alone, as is one missing from a rootfs we cannot write to
+pub fn mount(spec: &ContainerSpec, dir: BorrowedFd) -> io::Result<()> {
+    for file in FILES {
+        let target = Path::new("/etc").join(file);
+        if spec.mounts.iter().any(|m| Path::new(&m.destination) == target) {
+            continue;
+        }
+
+        // in a user namespace the rootfs usually belongs to a user the container cannot write as
+        if !target.exists()
+            && let Err(e) = fs::create_dir_all("/etc").and_then(|_| File::create(&target))
+        {
+            match e.kind() {
+                io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                    log::warn!("cannot create {}, not mounting it: {}", target.display(), e);
+                    continue;
+                }
+                _ => return Err(e),
+            }
+        }
+
+        let tree = open_tree(dir, file, false)?;
+        move_mount(tree.as_fd(), &target).map_err(|e| io::Error::other(format!("mounting {}: {}", target.display(), e)))?;
+    }
+
+    Ok(())
+}
+
+// with its own network namespace the container only has loopback to itself
+// sharing the host's, it gets the host's entries
+fn hosts(spec: &ContainerSpec, hostname: &str, private_network: bool) -> io::Result<String> {
+    let mut hosts = if private_network {
+        format!(
+            "127.0.0.1\tlocalhost\n::1\tlocalhost ip6-localhost ip6-loopback\n127.0.1.1\t{}\n",
+            hostname
+        )
+    } else {
+        read_host_file(HOST_HOSTS)?
+    };
+    if !hosts.is_empty() && !hosts.ends_with('\n') {
+        hosts.push('\n');
+    }
+
+    for extra in spec.extra_hosts.iter().flatten() {
+        hosts.push_str(&format!("{}\t{}\n", extra.ip, extra.hostnames.join(" ")));
+    }
+
+    Ok(hosts)
+}
+
+// what the spec leaves unset is taken from the host's resolv.conf
+// a resolver on the host's loopback, like systemd-resolved's stub, cannot be reached from a private network namespace
+fn resolv_conf(spec: &ContainerSpec, private_network: bool) -> io::Result<String> {
+    let host = read_host_file(HOST_RESOLV_CONF)?;
+    let dns = spec.dns.clone().unwrap_or_default();
+
+    let servers = match dns.servers {
+        Some(servers) => servers,
+        None => host_values(&host, "nameserver")
+            .into_iter()
+            .filter(|server| !private_network || server.parse::<IpAddr>().is_ok_and(|ip| !ip.is_loopback()))
+            .collect(),
+    };
+    // the last search or domain line wins, like for the resolver itself
+    let search = dns.search.unwrap_or_else(|| {
+        host.lines()
+            .rev()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|fields| matches!(fields.first(), Some(&"search" | &"domain")))
+            .map(|fields| fields[1..].iter().map(|domain| domain.to_string()).collect())
+            .unwrap_or_default()
+    });
+    let options = dns.options.unwrap_or_else(|| host_values(&host, "options"));
+
+    let mut resolv_conf = String::new();
+    for server in &servers {
+        resolv_conf.push_str(&format!("nameserver {}\n", server));
+    }
+    if !search.is_empty() {
+        resolv_conf.push_str(&format!("search {}\n", search.join(" ")));
+    }
+    if !options.is_empty() {
+        resolv_conf.push_str(&format!("options {}\n", options.join(" ")));
+    }
+
+    Ok(resolv_conf)
+}
+
+// the values of all lines starting with the keyword
+fn host_values(content: &str, keyword: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .filter(|fields| fields.first() == Some(&keyword))
+        .flat_map(|fields| fields[1..].iter().map(|value| value.to_string()).collect::<Vec<_>>())
+        .collect()
+}
+
+fn read_host_file(path: &str) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(e),
+    }
+}
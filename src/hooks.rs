@@ -0,0 +1,109 @@
+use crate::oci;
+use crate::spec::{ContainerSpec, Hook};
+use crate::syscall::pidfd_open;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::unistd::Pid;
+use std::io;
+use std::io::Write;
+use std::os::fd::AsFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+
+// the hook types in the order they run, also what `frogc features` reports
+pub const HOOK_TYPES: [&str; 6] = [
+    "prestart",
+    "createRuntime",
+    "createContainer",
+    "startContainer",
+    "poststart",
+    "poststop",
+];
+
+fn hooks<'a>(spec: &'a ContainerSpec, hook_type: &str) -> &'a [Hook] {
+    let Some(hooks) = &spec.hooks else {
+        return &[];
+    };
+
+    let group = match hook_type {
+        "prestart" => &hooks.prestart,
+        "createRuntime" => &hooks.create_runtime,
+        "createContainer" => &hooks.create_container,
+        "startContainer" => &hooks.start_container,
+        "poststart" => &hooks.poststart,
+        "poststop" => &hooks.poststop,
+        _ => &None,
+    };
+    group.as_deref().unwrap_or_default()
+}
+
+// runs the hooks of a type one after another, each gets the state of the container on stdin
+// the first hook that fails or exceeds its timeout fails the whole group
+pub fn run(spec: &ContainerSpec, hook_type: &str, state: &oci::State) -> io::Result<()> {
+    let hooks = hooks(spec, hook_type);
+    if hooks.is_empty() {
+        return Ok(());
+    }
+
+    let input = serde_json::to_vec(state)?;
+    for hook in hooks {
+        run_hook(hook, &input)
+            .map_err(|e| io::Error::other(format!("{} hook {}: {}", hook_type, hook.path, e)))?;
+    }
+
+    Ok(())
+}
+
+fn run_hook(hook: &Hook, input: &[u8]) -> io::Result<()> {
+    let mut command = Command::new(&hook.path);
+    if let Some((arg0, args)) = hook.args.as_deref().and_then(|args| args.split_first()) {
+        command.arg0(arg0).args(args);
+    }
+    command
+        .env_clear()
+        .envs(hook.env.iter().flatten().filter_map(|var| var.split_once('=')))
+        .stdin(Stdio::piped());
+
+    let mut child = command.spawn()?;
+
+    // the state is much smaller than a pipe buffer, a hook that does not read it cannot block us
+    if let Some(mut stdin) = child.stdin.take() {
+        match stdin.write_all(input) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => (),
+            r => r?,
+        }
+    }
+
+    let status = match hook.timeout {
+        Some(timeout) => wait_timeout(&mut child, Duration::from_secs(timeout))?,
+        None => child.wait()?,
+    };
+    if !status.success() {
+        return Err(io::Error::other(format!("exited with {}", status)));
+    }
+
+    Ok(())
+}
+
+// the hook is killed once the timeout expires
+fn wait_timeout(child: &mut Child, timeout: Duration) -> io::Result<ExitStatus> {
+    let pidfd = pidfd_open(Pid::from_raw(child.id() as i32))?;
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut fds = [PollFd::new(pidfd.as_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, PollTimeout::try_from(remaining).unwrap_or(PollTimeout::MAX)) {
+            Err(Errno::EINTR) => continue,
+            Ok(0) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::other(format!("timed out after {}s", timeout.as_secs())));
+            }
+            r => r?,
+        };
+
+        return child.wait();
+    }
+}
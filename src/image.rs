@@ -0,0 +1,449 @@
+use flate2::read::GzDecoder;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use nix::unistd::geteuid;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::{env, fs, io};
+
+const INDEX_FILE: &str = "index.json";
+const REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+const INDEX_MEDIA_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+
+// a whiteout hides the file of the same name from lower layers, an opaque whiteout all contents of its directory
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+// the parts of the OCI image spec needed to unpack an image
+#[derive(Debug, Deserialize)]
+pub struct Descriptor {
+    #[serde(rename = "mediaType", default)]
+    pub media_type: String,
+
+    #[serde(rename = "digest")]
+    pub digest: String,
+
+    #[serde(rename = "size")]
+    pub size: u64,
+
+    #[serde(rename = "annotations")]
+    pub annotations: Option<BTreeMap<String, String>>,
+
+    #[serde(rename = "platform")]
+    pub platform: Option<Platform>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Platform {
+    #[serde(rename = "architecture")]
+    pub architecture: String,
+
+    #[serde(rename = "os")]
+    pub os: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Index {
+    #[serde(rename = "manifests")]
+    pub manifests: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Manifest {
+    #[serde(rename = "config")]
+    pub config: Descriptor,
+
+    #[serde(rename = "layers")]
+    pub layers: Vec<Descriptor>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageConfig {
+    #[serde(rename = "config")]
+    pub config: Option<ExecutionConfig>,
+}
+
+// what the image says about how to run it, every field is optional
+#[derive(Debug, Deserialize, Default)]
+pub struct ExecutionConfig {
+    #[serde(rename = "User")]
+    pub user: Option<String>,
+
+    #[serde(rename = "Env")]
+    pub env: Option<Vec<String>>,
+
+    #[serde(rename = "Entrypoint")]
+    pub entrypoint: Option<Vec<String>>,
+
+    #[serde(rename = "Cmd")]
+    pub cmd: Option<Vec<String>>,
+
+    #[serde(rename = "WorkingDir")]
+    pub working_dir: Option<String>,
+}
+
+// a directory in the OCI image layout: index.json plus the blobs it refers to under blobs/<algorithm>/<hex>
+pub struct Layout {
+    dir: PathBuf,
+}
+
+impl Layout {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    // finds the manifest tagged with tag, or the only one in the index without a tag
+    // an index of manifests for several platforms resolves to the one for the host
+    pub fn manifest(&self, tag: Option<&str>) -> io::Result<Manifest> {
+        let content = fs::read(self.dir.join(INDEX_FILE))
+            .map_err(|e| io::Error::new(e.kind(), format!("reading {}: {}", INDEX_FILE, e)))?;
+        let index: Index = serde_json::from_slice(&content)?;
+
+        let ref_name = |d: &Descriptor| d.annotations.as_ref().and_then(|a| a.get(REF_NAME_ANNOTATION)).cloned();
+        let descriptor = match tag {
+            Some(tag) => index
+                .manifests
+                .into_iter()
+                .find(|d| ref_name(d).as_deref() == Some(tag))
+                .ok_or_else(|| io::Error::other(format!("no image tagged {} in the layout", tag)))?,
+            None if index.manifests.len() == 1 => index.manifests.into_iter().next().unwrap(),
+            None => {
+                let tags: Vec<String> = index.manifests.iter().filter_map(ref_name).collect();
+                return Err(io::Error::other(format!(
+                    "the layout has {} images, select one by tag: {}",
+                    index.manifests.len(),
+                    tags.join(", ")
+                )));
+            }
+        };
+
+        self.resolve(descriptor)
+    }
+
+    pub fn config(&self, manifest: &Manifest) -> io::Result<ImageConfig> {
+        self.read_json(&manifest.config)
+    }
+
+    // applies the layers of the image in order on top of each other in dest
+    pub fn unpack(&self, manifest: &Manifest, dest: &Path) -> io::Result<()> {
+        for layer in &manifest.layers {
+            // a corrupt layer must not be half applied, so the blob is checked before reading it for real
+            let path = self.verified_blob(layer)?;
+            let file = File::open(&path)?;
+            let reader: Box<dyn Read> = match compression(&layer.media_type)? {
+                Compression::None => Box::new(file),
+                Compression::Gzip => Box::new(GzDecoder::new(file)),
+                Compression::Zstd => Box::new(zstd::Decoder::new(file)?),
+            };
+
+            apply_layer(reader, dest).map_err(|e| io::Error::other(format!("applying layer {}: {}", layer.digest, e)))?;
+        }
+
+        Ok(())
+    }
+
+    fn resolve(&self, descriptor: Descriptor) -> io::Result<Manifest> {
+        if !INDEX_MEDIA_TYPES.contains(&descriptor.media_type.as_str()) {
+            return self.read_json(&descriptor);
+        }
+
+        let index: Index = self.read_json(&descriptor)?;
+        let (os, architecture) = host_platform();
+        let descriptor = index
+            .manifests
+            .into_iter()
+            .find(|d| d.platform.as_ref().is_some_and(|p| p.os == os && p.architecture == architecture))
+            .ok_or_else(|| io::Error::other(format!("the image has no manifest for {}/{}", os, architecture)))?;
+
+        self.resolve(descriptor)
+    }
+
+    fn read_json<T: DeserializeOwned>(&self, descriptor: &Descriptor) -> io::Result<T> {
+        let content = fs::read(self.verified_blob(descriptor)?)?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    // the path of the blob a descriptor refers to, once its size and digest have been checked
+    fn verified_blob(&self, descriptor: &Descriptor) -> io::Result<PathBuf> {
+        let (algorithm, hex) = descriptor
+            .digest
+            .split_once(':')
+            .ok_or_else(|| io::Error::other(format!("invalid digest {}", descriptor.digest)))?;
+        if algorithm != "sha256" {
+            return Err(io::Error::other(format!("unsupported digest algorithm {}", algorithm)));
+        }
+        // the digest becomes a path, it must not be able to point anywhere else
+        if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(io::Error::other(format!("invalid digest {}", descriptor.digest)));
+        }
+
+        let path = self.dir.join("blobs").join(algorithm).join(hex);
+        let mut file = File::open(&path)
+            .map_err(|e| io::Error::new(e.kind(), format!("opening blob {}: {}", descriptor.digest, e)))?;
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut file, &mut hasher)?;
+
+        if size != descriptor.size {
+            return Err(io::Error::other(format!(
+                "blob {} has {} bytes, expected {}",
+                descriptor.digest, size, descriptor.size
+            )));
+        }
+        if format!("{:x}", hasher.finalize()) != hex.to_lowercase() {
+            return Err(io::Error::other(format!("blob {} does not match its digest", descriptor.digest)));
+        }
+
+        Ok(path)
+    }
+}
+
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+fn compression(media_type: &str) -> io::Result<Compression> {
+    match media_type {
+        "application/vnd.oci.image.layer.v1.tar" | "application/vnd.oci.image.layer.nondistributable.v1.tar" => {
+            Ok(Compression::None)
+        }
+        "application/vnd.oci.image.layer.v1.tar+gzip"
+        | "application/vnd.oci.image.layer.nondistributable.v1.tar+gzip"
+        | "application/vnd.docker.image.rootfs.diff.tar.gzip" => Ok(Compression::Gzip),
+        "application/vnd.oci.image.layer.v1.tar+zstd"
+        | "application/vnd.oci.image.layer.nondistributable.v1.tar+zstd" => Ok(Compression::Zstd),
+        t => Err(io::Error::other(format!("unsupported layer media type {}", t))),
+    }
+}
+
+// the names the image spec uses for the host's platform
+fn host_platform() -> (&'static str, &'static str) {
+    let architecture = match env::consts::ARCH {
+        "x86_64" => "amd64",
+        "x86" => "386",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64le",
+        arch => arch,
+    };
+    ("linux", architecture)
+}
+
+// whiteouts only apply to lower layers, so what this layer itself unpacked is remembered and left alone
+fn apply_layer(reader: impl Read, dest: &Path) -> io::Result<()> {
+    let dest = &fs::canonicalize(dest)?;
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    // only root can give files away, anyone else unpacks everything as themselves
+    archive.set_preserve_ownerships(geteuid().is_root());
+    archive.set_preserve_mtime(true);
+    archive.set_unpack_xattrs(true);
+    archive.set_overwrite(true);
+
+    let mut unpacked = HashSet::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = clean_path(&entry.path()?)?;
+        let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) else {
+            continue;
+        };
+        let parent = path.parent().unwrap_or(Path::new(""));
+
+        if name == OPAQUE_WHITEOUT {
+            check_inside(dest, &dest.join(parent))?;
+            remove_lower_contents(&dest.join(parent), parent, &unpacked)?;
+            continue;
+        }
+        if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            // .wh..wh.* entries carry metadata of the layer's creator, they do not hide anything
+            if hidden.starts_with(WHITEOUT_PREFIX) {
+                continue;
+            }
+            // .wh... would otherwise remove the parent of dest
+            if hidden.is_empty() || hidden == "." || hidden == ".." || hidden.contains('/') {
+                return Err(io::Error::other(format!("invalid whiteout {} in layer", path.display())));
+            }
+
+            let hidden = parent.join(hidden);
+            if !unpacked.contains(&hidden) {
+                let target = dest.join(&hidden);
+                check_inside(dest, target.parent().unwrap_or(dest))?;
+                remove_path(&target)?;
+            }
+            continue;
+        }
+
+        // a lower layer may have something else at the path, only directories are merged
+        let target = dest.join(&path);
+        check_inside(dest, target.parent().unwrap_or(dest))?;
+        if let Ok(meta) = fs::symlink_metadata(&target)
+            && !(meta.is_dir() && entry.header().entry_type().is_dir())
+        {
+            remove_path(&target)?;
+        }
+
+        entry.unpack_in(dest)?;
+        unpacked.insert(path);
+    }
+
+    Ok(())
+}
+
+// the path of an entry relative to the root of the layer, anything leaving the root is refused
+fn clean_path(path: &Path) -> io::Result<PathBuf> {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => clean.push(part),
+            Component::RootDir | Component::CurDir => (),
+            _ => return Err(io::Error::other(format!("invalid path {} in layer", path.display()))),
+        }
+    }
+    Ok(clean)
+}
+
+// a layer can turn a directory of a lower layer into a symlink, removing through it could delete host files
+// tar itself checks this for the entries it unpacks, we have to for the paths we remove
+fn check_inside(dest: &Path, dir: &Path) -> io::Result<()> {
+    match fs::canonicalize(dir) {
+        Ok(real) if !real.starts_with(dest) => Err(io::Error::other(format!(
+            "{} leads out of the rootfs",
+            dir.display()
+        ))),
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn remove_lower_contents(dir: &Path, relative: &Path, unpacked: &HashSet<PathBuf>) -> io::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        if !unpacked.contains(&relative) {
+            remove_path(&entry.path())?;
+        } else if entry.file_type()?.is_dir() {
+            remove_lower_contents(&entry.path(), &relative, unpacked)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    let result = match fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) => Err(e),
+    };
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        r => r,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    // a directory of its own for every test, below one that stands in for the host
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("frogc-image-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("dest")).unwrap();
+        fs::write(dir.join("host-file"), "host").unwrap();
+        dir
+    }
+
+    fn layer(entries: &[(&str, tar::EntryType, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(vec![]);
+        for (path, entry_type, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(*entry_type);
+            header.set_mode(0o755);
+            header.set_uid(geteuid().as_raw() as u64);
+            header.set_gid(0);
+            header.set_mtime(0);
+            if entry_type.is_symlink() {
+                header.set_size(0);
+                builder.append_link(&mut header, path, data).unwrap();
+            } else {
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, path, data.as_bytes()).unwrap();
+            }
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn whiteout(name: &str) -> Vec<u8> {
+        layer(&[(name, tar::EntryType::Regular, "")])
+    }
+
+    #[test]
+    fn whiteout_of_parent_is_refused() {
+        let dir = scratch("parent");
+        let dest = dir.join("dest");
+
+        for name in [".wh...", ".wh..", ".wh."] {
+            assert!(apply_layer(whiteout(name).as_slice(), &dest).is_err(), "{} was applied", name);
+            assert!(dir.join("host-file").exists(), "{} removed a host file", name);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn whiteout_through_symlink_is_refused() {
+        let dir = scratch("symlink");
+        let dest = dir.join("dest");
+
+        let link = layer(&[("link", tar::EntryType::Symlink, dir.to_str().unwrap())]);
+        apply_layer(link.as_slice(), &dest).unwrap();
+        assert!(apply_layer(whiteout("link/.wh.host-file").as_slice(), &dest).is_err());
+        assert!(dir.join("host-file").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn whiteouts_apply_to_lower_layers() {
+        let dir = scratch("lower");
+        let dest = dir.join("dest");
+
+        let lower = layer(&[
+            ("gone", tar::EntryType::Regular, "x"),
+            ("kept", tar::EntryType::Regular, "x"),
+            ("d", tar::EntryType::Directory, ""),
+            ("d/old", tar::EntryType::Regular, "x"),
+        ]);
+        let upper = layer(&[
+            (".wh.gone", tar::EntryType::Regular, ""),
+            (".wh..wh.plnk", tar::EntryType::Regular, ""),
+            ("d", tar::EntryType::Directory, ""),
+            ("d/new", tar::EntryType::Regular, "x"),
+            ("d/.wh..wh..opq", tar::EntryType::Regular, ""),
+        ]);
+        apply_layer(lower.as_slice(), &dest).unwrap();
+        apply_layer(upper.as_slice(), &dest).unwrap();
+
+        assert!(!dest.join("gone").exists());
+        assert!(dest.join("kept").exists());
+        assert!(!dest.join("d/old").exists());
+        assert!(dest.join("d/new").exists());
+        assert!(!dest.join(".wh..wh.plnk").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+use crate::commands::start;
+use crate::errors::{ContainerError, WrapError};
+use nix::errno::Errno;
+use nix::sys::signal::{kill, SigSet, SigmaskHow, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{fork, getpid, setpgid, tcsetpgrp, ForkResult, Pid};
+use std::{io, process};
+
+// a minimal init that stays PID 1 of the container's PID namespace and runs the command as its child
+// the kernel drops signals sent to PID 1 unless it installed a handler and reparents every orphan to it,
+// so a command that was not written to be PID 1 would neither stop on SIGTERM nor reap zombies
+// exits with the exit code of the command once it exited
+pub fn run(terminal: bool, exec: impl FnOnce() -> Result<(), ContainerError>) -> Result<(), ContainerError> {
+    // all signals are blocked so we can wait for them synchronously, the child restores the mask before exec
+    let original = SigSet::all()
+        .thread_swap_mask(SigmaskHow::SIG_BLOCK)
+        .map_err(ContainerError::wrap)?;
+
+    match unsafe { fork() }.map_err(ContainerError::wrap)? {
+        ForkResult::Child => {
+            // its own process group lets us signal the command together with everything it spawned
+            setpgid(Pid::from_raw(0), Pid::from_raw(0)).map_err(ContainerError::wrap)?;
+
+            // keys like ctrl-c have to reach the command, so its group becomes the terminal's foreground group
+            if terminal {
+                tcsetpgrp(io::stdin(), getpid())
+                    .map_err(WrapError::wrapper("setting foreground process group"))
+                    .map_err(ContainerError::wrap)?;
+            }
+
+            original.thread_set_mask().map_err(ContainerError::wrap)?;
+            exec()
+        }
+        ForkResult::Parent { child } => loop {
+            let signal = SigSet::all().wait().map_err(ContainerError::wrap)?;
+            if signal != Signal::SIGCHLD {
+                forward(child, signal);
+                continue;
+            }
+
+            if let Some(status) = reap(child) {
+                process::exit(start::exit_code(status));
+            }
+        },
+    }
+}
+
+fn forward(child: Pid, signal: Signal) {
+    // the command may already have exited while its group lives on, or the other way around
+    match kill(Pid::from_raw(-child.as_raw()), signal) {
+        Err(Errno::ESRCH) => {
+            let _ = kill(child, signal);
+        }
+        Err(e) => log::warn!("forwarding {} to the container command: {}", signal, e),
+        Ok(_) => (),
+    }
+}
+
+// reaps every exited process, returns the command's wait status once it is among them
+fn reap(child: Pid) -> Option<WaitStatus> {
+    let mut child_status = None;
+    loop {
+        match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::StillAlive) | Err(_) => return child_status,
+            Ok(status) if status.pid() == Some(child) => child_status = Some(status),
+            Ok(_) => (),
+        }
+    }
+}
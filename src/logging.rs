@@ -0,0 +1,184 @@
+use crate::context::FrogContext;
+use crate::spec::LogConfig;
+use chrono::{SecondsFormat, Utc};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+const DEFAULT_LOG_FILE: &str = "container.log";
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_MAX_FILES: u32 = 5;
+
+// longer lines are split into several partial entries, like the CRI does
+const MAX_LINE_LENGTH: usize = 16 * 1024;
+
+// relative log paths are resolved against the container run dir
+pub fn log_path(context: &FrogContext, container_id: &str, config: &LogConfig) -> PathBuf {
+    context
+        .container_run_dir(container_id)
+        .join(config.path.as_deref().unwrap_or(DEFAULT_LOG_FILE))
+}
+
+pub fn max_files(config: &LogConfig) -> u32 {
+    config.max_files.unwrap_or(DEFAULT_MAX_FILES).max(1)
+}
+
+// rotated files are numbered from 1 (newest) to max_files - 1 (oldest)
+pub fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+// a single entry in the CRI log format: "<timestamp> <stream> <tag> <message>"
+// the tag is F for a full line and P for a partial one that continues in the next entry
+pub struct LogEntry<'a> {
+    pub stream: &'a str,
+    pub partial: bool,
+    pub message: &'a str,
+}
+
+impl<'a> LogEntry<'a> {
+    pub fn parse(line: &'a str) -> Option<Self> {
+        let mut parts = line.splitn(4, ' ');
+        let _timestamp = parts.next()?;
+        let stream = parts.next()?;
+        let partial = match parts.next()? {
+            "P" => true,
+            "F" => false,
+            _ => return None,
+        };
+        let message = parts.next().unwrap_or("");
+
+        Some(Self { stream, partial, message })
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: u32,
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open(path: PathBuf, config: &LogConfig) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path,
+            max_size: config.max_size.unwrap_or(DEFAULT_MAX_SIZE),
+            max_files: max_files(config),
+            file,
+            size,
+        })
+    }
+
+    fn write_entry(&mut self, stream: &str, partial: bool, message: &[u8]) -> io::Result<()> {
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let tag = if partial { "P" } else { "F" };
+
+        let mut entry = format!("{} {} {} ", timestamp, stream, tag).into_bytes();
+        entry.extend_from_slice(message);
+        entry.push(b'\n');
+
+        if self.size > 0 && self.size + entry.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        self.file.write_all(&entry)?;
+        self.size += entry.len() as u64;
+
+        Ok(())
+    }
+
+    // shifts every rotated file one up, dropping the oldest, and starts a new current file
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.max_files > 1 {
+            for n in (1..self.max_files - 1).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+            fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+
+        Ok(())
+    }
+}
+
+// splits the container's output into lines and writes them to the log file
+// output without a trailing newline is kept back until the line is completed or the stream is closed
+pub struct Logger {
+    file: LogFile,
+    pending: HashMap<&'static str, Vec<u8>>,
+}
+
+impl Logger {
+    pub fn open(path: PathBuf, config: &LogConfig) -> io::Result<Self> {
+        Ok(Self {
+            file: LogFile::open(path, config)?,
+            pending: HashMap::new(),
+        })
+    }
+
+    pub fn write(&mut self, stream: &'static str, data: &[u8]) -> io::Result<()> {
+        let pending = self.pending.entry(stream).or_default();
+        pending.extend_from_slice(data);
+
+        let Some(end) = pending.iter().rposition(|b| *b == b'\n') else {
+            // a line that never ends is written in chunks instead of buffering it forever
+            if pending.len() >= MAX_LINE_LENGTH {
+                let line = std::mem::take(pending);
+                return self.write_line(stream, &line, false);
+            }
+            return Ok(());
+        };
+
+        let rest = pending.split_off(end + 1);
+        let lines = std::mem::replace(pending, rest);
+        for line in lines[..end].split(|b| *b == b'\n') {
+            self.write_line(stream, line, true)?;
+        }
+
+        Ok(())
+    }
+
+    // writes out what is left of a stream once it is closed, as a partial line
+    pub fn close(&mut self, stream: &'static str) -> io::Result<()> {
+        match self.pending.remove(stream) {
+            Some(line) if !line.is_empty() => self.write_line(stream, &line, false),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_line(&mut self, stream: &str, line: &[u8], complete: bool) -> io::Result<()> {
+        let mut chunks = line.chunks(MAX_LINE_LENGTH).peekable();
+        if chunks.peek().is_none() {
+            return self.file.write_entry(stream, !complete, &[]);
+        }
+
+        while let Some(chunk) = chunks.next() {
+            let last = chunks.peek().is_none();
+            self.file.write_entry(stream, !(last && complete), chunk)?;
+        }
+
+        Ok(())
+    }
+}
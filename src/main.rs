@@ -6,45 +6,184 @@ mod errors;
 mod reexec;
 mod types;
 mod syscall;
+mod cgroup;
+mod stats;
+mod console;
+mod logging;
+mod shim;
+mod init;
+mod oci;
+mod namespaces;
+mod hooks;
+mod validate;
+mod devices;
+mod bpf;
+mod paths;
+mod etc;
+mod sysctl;
+mod capabilities;
+mod rootfs;
+mod image;
 
-use std::process;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::{io, process};
+use chrono::Utc;
 use clap::Parser;
 use cli::FrogCli;
-use crate::cli::{Commands, ReExecCommands};
+use env_logger::Target;
+use log::LevelFilter;
+use crate::cli::{Commands, ImageCommands, LogFormat, ReExecCommands};
+use crate::commands::exec::Exec;
 use crate::errors::ContainerError;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
     let cli = FrogCli::parse();
+    let mut log_to_file = cli.log.is_some();
+    if let Err(e) = init_logger(cli.log.as_deref(), cli.log_format, cli.debug) {
+        // in a user namespace the container's root may not be allowed to open the log of the frogc that started it
+        if !matches!(cli.command, Commands::ReExec { .. }) || init_logger(None, cli.log_format, cli.debug).is_err() {
+            eprintln!("Error: opening log: {}", e);
+            process::exit(1);
+        }
+        log::debug!("opening log, logging to stderr instead: {}", e);
+        log_to_file = false;
+    }
+    if cli.systemd_cgroup {
+        log::warn!("--systemd-cgroup is not supported, using cgroupfs");
+    }
 
     let run_dir = cli.run_dir.unwrap_or_else(|| "/run/frogc".to_string());
-    let context = context::FrogContext::new(run_dir);
+    let context = context::FrogContext::new(run_dir).with_logging(cli.log.clone(), cli.log_format, cli.debug);
 
     let err = match cli.command {
-        Commands::Create { spec, container_id } => {
-            commands::create::run(context, spec, container_id)
+        Commands::Create { spec, bundle, console_socket, pid_file, log_path, container_id } => {
+            commands::create::run(context, spec, bundle, container_id, console_socket, pid_file, log_path)
+        },
+        Commands::Start { foreground, container_id } => {
+            match commands::start::run(context, container_id, foreground) {
+                Ok(code) if foreground => process::exit(code),
+                r => r.map(drop),
+            }
+        },
+        Commands::Attach { read_only, detach_keys, container_id } => {
+            match commands::attach::run(context, container_id, read_only, detach_keys) {
+                Ok(code) => process::exit(code),
+                Err(e) => Err(e),
+            }
+        },
+        Commands::Kill { all, container_id, signal } => {
+            commands::kill::run(context, container_id, signal, all)
+        },
+        Commands::Resize { rows, cols, container_id } => {
+            commands::resize::run(context, container_id, rows, cols)
+        },
+        Commands::State { container_id } => {
+            commands::state::run(context, container_id)
+        },
+        Commands::List { format } => {
+            commands::list::run(context, format)
+        },
+        Commands::Logs { follow, tail, container_id } => {
+            commands::logs::run(context, container_id, follow, tail)
+        },
+        Commands::Run { spec, interactive, tty, container_id } => {
+            match commands::run::run(context, spec, container_id, interactive, tty) {
+                Ok(code) => process::exit(code),
+                Err(e) => Err(e),
+            }
+        },
+        Commands::Pause { container_id } => {
+            commands::pause::run(context, container_id)
+        },
+        Commands::Resume { container_id } => {
+            commands::resume::run(context, container_id)
+        },
+        Commands::Stats { container_id } => {
+            commands::stats::run(context, container_id)
+        },
+        Commands::Events { interval, container_id } => {
+            commands::events::run(context, container_id, interval)
+        },
+        Commands::Ps { format, container_id } => {
+            commands::ps::run(context, container_id, format)
         },
-        Commands::Start { container_id } => {
-            commands::start::run(context, container_id)
+        Commands::Delete { force, container_id } => {
+            commands::delete::run(context, container_id, force)
+        },
+        Commands::Exec { process, detach, pid_file, console_socket, tty, cwd, env, container_id, command } => {
+            let exec = Exec { container_id, process, command, tty, cwd, env, detach, pid_file, console_socket };
+            match exec.run(context) {
+                Ok(code) => process::exit(code),
+                Err(e) => Err(e),
+            }
+        },
+        Commands::Spec { output, rootfs, rootless } => {
+            commands::spec::run(output, rootfs, rootless)
+        },
+        Commands::Validate { spec, bundle } => {
+            commands::validate::run(spec, bundle)
+        },
+        Commands::Features => {
+            commands::features::run()
+        },
+        Commands::Image { command } => {
+            match command {
+                ImageCommands::Unpack { image, dest, spec } => {
+                    commands::image::unpack(image, dest, spec)
+                }
+            }
         },
         Commands::ReExec {command} => {
             match command {
-                ReExecCommands::Start { container_id } => {
-                    commands::reexec_start::run(context, container_id)
+                ReExecCommands::Start { sync_fd, container_id } => {
+                    commands::reexec_start::run(context, container_id, sync_fd)
                 }
             }
         }
     };
 
     if let Err(e) = err {
+        // runtimes driving us like runc read the error from the log
+        if log_to_file {
+            log::error!("{}", e);
+        }
         eprintln!("Error: {}", e);
         match e {
             ContainerError::AlreadyExists => process::exit(2),
             ContainerError::NotFound => process::exit(3),
             ContainerError::InvalidState(_) => process::exit(4),
+            ContainerError::InvalidSpec(_) => process::exit(5),
             _ => process::exit(1),
         }
     }
 
     Ok(())
 }
+
+// like runc, messages go to stderr unless --log is given, and are written as JSON lines with --log-format json
+fn init_logger(log: Option<&str>, format: LogFormat, debug: bool) -> io::Result<()> {
+    let mut builder = env_logger::Builder::from_default_env();
+    if debug {
+        builder.filter_level(LevelFilter::Debug);
+    }
+
+    if let Some(path) = log {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        builder.target(Target::Pipe(Box::new(file)));
+    }
+
+    if let LogFormat::Json = format {
+        builder.format(|buf, record| {
+            let entry = serde_json::json!({
+                "level": record.level().as_str().to_lowercase(),
+                "msg": record.args().to_string(),
+                "time": Utc::now().to_rfc3339(),
+            });
+            writeln!(buf, "{}", entry)
+        });
+    }
+
+    builder.init();
+    Ok(())
+}
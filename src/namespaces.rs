@@ -0,0 +1,151 @@
+use crate::spec::{ContainerSpec, LinuxIdMapping, Namespace};
+use nix::fcntl::OFlag;
+use nix::libc;
+use nix::sched::{clone, setns, unshare, CloneFlags};
+use nix::sys::wait::waitpid;
+use nix::unistd::{geteuid, pipe2, read, write, Pid};
+use std::fs::File;
+use std::{fs, io};
+
+pub const NAMESPACE_TYPES: [&str; 7] = ["mount", "pid", "uts", "ipc", "network", "user", "cgroup"];
+
+// the namespaces a container gets when its spec does not list any
+pub const DEFAULT_NAMESPACES: [&str; 4] = ["mount", "pid", "uts", "ipc"];
+
+// the process holding a user namespace only waits on a pipe
+const STACK_SIZE: usize = 64 * 1024;
+
+fn clone_flag(ns_type: &str) -> Option<CloneFlags> {
+    match ns_type {
+        "mount" => Some(CloneFlags::CLONE_NEWNS),
+        "pid" => Some(CloneFlags::CLONE_NEWPID),
+        "uts" => Some(CloneFlags::CLONE_NEWUTS),
+        "ipc" => Some(CloneFlags::CLONE_NEWIPC),
+        "network" => Some(CloneFlags::CLONE_NEWNET),
+        "user" => Some(CloneFlags::CLONE_NEWUSER),
+        "cgroup" => Some(CloneFlags::CLONE_NEWCGROUP),
+        _ => None,
+    }
+}
+
+fn namespaces(spec: &ContainerSpec) -> Vec<Namespace> {
+    match &spec.namespaces {
+        Some(namespaces) => namespaces.clone(),
+        None => DEFAULT_NAMESPACES
+            .iter()
+            .map(|ns_type| Namespace {
+                ns_type: ns_type.to_string(),
+                path: None,
+            })
+            .collect(),
+    }
+}
+
+// whether the container gets a new namespace of this type, rather than joining one or sharing the host's
+pub fn is_private(spec: &ContainerSpec, ns_type: &str) -> bool {
+    namespaces(spec)
+        .iter()
+        .any(|ns| ns.ns_type == ns_type && ns.path.is_none())
+}
+
+// the flags to clone the container process with
+// the cgroup namespace is left out, it is unshared once the process has been moved into its cgroup
+pub fn clone_flags(spec: &ContainerSpec) -> io::Result<CloneFlags> {
+    if !is_private(spec, "mount") {
+        return Err(io::Error::other("containers need their own mount namespace"));
+    }
+
+    let mut flags = CloneFlags::empty();
+    for ns in namespaces(spec).iter().filter(|ns| ns.path.is_none()) {
+        let flag = clone_flag(&ns.ns_type)
+            .ok_or_else(|| io::Error::other(format!("unknown namespace type {}", ns.ns_type)))?;
+        flags |= flag;
+    }
+
+    Ok(flags.difference(CloneFlags::CLONE_NEWCGROUP))
+}
+
+// joins the namespaces given by path and unshares the cgroup namespace
+// must be called by the container process before setting up its mounts
+pub fn enter(spec: &ContainerSpec) -> io::Result<()> {
+    for ns in namespaces(spec) {
+        let Some(path) = &ns.path else {
+            continue;
+        };
+
+        // the mount, pid and user namespaces of a process cannot be changed after it has been set up
+        if !matches!(ns.ns_type.as_str(), "uts" | "ipc" | "network" | "cgroup") {
+            return Err(io::Error::other(format!(
+                "joining an existing {} namespace is not supported",
+                ns.ns_type
+            )));
+        }
+
+        let file = File::open(path)?;
+        setns(file, clone_flag(&ns.ns_type).unwrap_or(CloneFlags::empty()))?;
+    }
+
+    if is_private(spec, "cgroup") {
+        unshare(CloneFlags::CLONE_NEWCGROUP)?;
+    }
+
+    Ok(())
+}
+
+// writes the id mappings of the container's user namespace, the process has to wait until this is done
+pub fn write_id_mappings(pid: Pid, spec: &ContainerSpec) -> io::Result<()> {
+    if !is_private(spec, "user") {
+        return Ok(());
+    }
+
+    // an unprivileged user may only write a gid mapping after giving up setgroups in the namespace
+    if !geteuid().is_root() {
+        fs::write(format!("/proc/{}/setgroups", pid), "deny")?;
+    }
+
+    write_id_mapping(pid, "uid_map", spec.uid_mappings.as_deref().unwrap_or_default())?;
+    write_id_mapping(pid, "gid_map", spec.gid_mappings.as_deref().unwrap_or_default())
+}
+
+// creates a user namespace with the container's id mappings, for idmapped mounts made outside the container
+// a namespace needs a process to be created in, which only lives until we hold a reference to the namespace
+pub fn new_user_namespace(spec: &ContainerSpec) -> io::Result<File> {
+    let (ready_read, ready_write) = pipe2(OFlag::O_CLOEXEC)?;
+    let mut stack = vec![0u8; STACK_SIZE];
+    let pid = unsafe {
+        clone(
+            Box::new(|| {
+                let mut buf = [0u8; 1];
+                let _ = read(&ready_read, &mut buf);
+                0
+            }),
+            &mut stack,
+            CloneFlags::CLONE_NEWUSER,
+            Some(libc::SIGCHLD),
+        )
+    }?;
+
+    let userns = write_id_mappings(pid, spec).and_then(|()| File::open(format!("/proc/{}/ns/user", pid)));
+
+    // the child has a copy of the write end, so it is woken up by a byte rather than EOF
+    let _ = write(&ready_write, &[0]);
+    waitpid(pid, None)?;
+
+    userns
+}
+
+fn write_id_mapping(pid: Pid, file: &str, mappings: &[LinuxIdMapping]) -> io::Result<()> {
+    if mappings.is_empty() {
+        return Err(io::Error::other(format!(
+            "the user namespace needs {} entries",
+            file
+        )));
+    }
+
+    // the whole mapping has to be written at once
+    let content: String = mappings
+        .iter()
+        .map(|m| format!("{} {} {}\n", m.container_id, m.host_id, m.size))
+        .collect();
+    fs::write(format!("/proc/{}/{}", pid, file), content)
+}
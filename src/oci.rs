@@ -0,0 +1,428 @@
+use crate::devices;
+use crate::errors::{ContainerError, WrapError};
+use crate::rootfs;
+use crate::namespaces::NAMESPACE_TYPES;
+use crate::spec;
+use crate::spec::{ContainerSpec, ContainerState, LinuxIdMapping};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub const OCI_VERSION_MIN: &str = "1.0.0";
+pub const OCI_VERSION_MAX: &str = "1.2.0";
+const CONFIG_FILE: &str = "config.json";
+
+// the parts of the OCI runtime spec (config.json) that frogc understands
+// every level collects the fields we do not support, so they can be reported instead of silently dropped
+#[derive(Debug, Deserialize)]
+pub struct Spec {
+    #[serde(rename = "ociVersion")]
+    pub oci_version: String,
+
+    #[serde(rename = "root")]
+    pub root: Option<Root>,
+
+    #[serde(rename = "process")]
+    pub process: Option<Process>,
+
+    #[serde(rename = "hostname")]
+    pub hostname: Option<String>,
+
+    #[serde(rename = "domainname")]
+    pub domainname: Option<String>,
+
+    #[serde(rename = "mounts", default)]
+    pub mounts: Vec<Mount>,
+
+    #[serde(rename = "annotations")]
+    pub annotations: Option<BTreeMap<String, String>>,
+
+    #[serde(rename = "linux")]
+    pub linux: Option<Linux>,
+
+    #[serde(rename = "hooks")]
+    pub hooks: Option<spec::Hooks>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+// the state of a container as defined by the OCI runtime spec, printed by state and passed to hooks
+#[derive(Debug, Serialize)]
+pub struct State {
+    #[serde(rename = "ociVersion")]
+    pub oci_version: String,
+
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "status")]
+    pub status: String,
+
+    // 0 once the container process is gone, like runc reports it
+    #[serde(rename = "pid")]
+    pub pid: u32,
+
+    // containers created from a spec file have no bundle
+    #[serde(rename = "bundle")]
+    pub bundle: String,
+
+    #[serde(rename = "rootfs")]
+    pub rootfs: String,
+
+    #[serde(rename = "created", skip_serializing_if = "Option::is_none")]
+    pub created: Option<DateTime<Utc>>,
+
+    #[serde(rename = "annotations", skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<BTreeMap<String, String>>,
+}
+
+impl State {
+    pub fn new(state: &ContainerState) -> Self {
+        Self {
+            oci_version: OCI_VERSION_MAX.to_string(),
+            id: state.id.clone(),
+            status: state.status.clone(),
+            pid: state.pid.unwrap_or(0),
+            bundle: state.bundle.clone().unwrap_or_default(),
+            // the state is only reported here, setting up the container fails on a missing rootfs
+            rootfs: rootfs::path(state).unwrap_or_default().to_string(),
+            created: state.created_at,
+            annotations: state.spec.annotations.clone(),
+        }
+    }
+
+    pub fn with_status(mut self, status: &str) -> Self {
+        self.status = status.to_string();
+        self
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Root {
+    #[serde(rename = "path")]
+    pub path: String,
+
+    #[serde(rename = "readonly")]
+    pub readonly: Option<bool>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Process {
+    #[serde(rename = "terminal")]
+    pub terminal: Option<bool>,
+
+    #[serde(rename = "user")]
+    pub user: User,
+
+    #[serde(rename = "args", default)]
+    pub args: Vec<String>,
+
+    #[serde(rename = "env")]
+    pub env: Option<Vec<String>>,
+
+    #[serde(rename = "cwd")]
+    pub cwd: String,
+
+    #[serde(rename = "capabilities")]
+    pub capabilities: Option<Capabilities>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+// frogc keeps a single set, the bounding set limits whatever effective, permitted and inheritable could hold
+#[derive(Debug, Deserialize)]
+pub struct Capabilities {
+    #[serde(rename = "bounding")]
+    pub bounding: Option<Vec<String>>,
+
+    #[serde(rename = "ambient")]
+    pub ambient: Option<Vec<String>>,
+}
+
+impl Capabilities {
+    pub fn into_list(self) -> Option<Vec<String>> {
+        if self.ambient.is_some_and(|ambient| !ambient.is_empty()) {
+            log::warn!("ignoring process.capabilities.ambient, ambient capabilities are not supported");
+        }
+        self.bounding
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct User {
+    #[serde(rename = "uid")]
+    pub uid: u32,
+
+    #[serde(rename = "gid")]
+    pub gid: u32,
+
+    #[serde(rename = "additionalGids")]
+    pub additional_gids: Option<Vec<u32>>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Mount {
+    #[serde(rename = "destination")]
+    pub destination: String,
+
+    #[serde(rename = "type")]
+    pub fs_type: Option<String>,
+
+    #[serde(rename = "source")]
+    pub source: Option<String>,
+
+    #[serde(rename = "options", default)]
+    pub options: Vec<String>,
+
+    #[serde(rename = "uidMappings")]
+    pub uid_mappings: Option<Vec<LinuxIdMapping>>,
+
+    #[serde(rename = "gidMappings")]
+    pub gid_mappings: Option<Vec<LinuxIdMapping>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Linux {
+    #[serde(rename = "namespaces", default)]
+    pub namespaces: Vec<spec::Namespace>,
+
+    #[serde(rename = "uidMappings")]
+    pub uid_mappings: Option<Vec<LinuxIdMapping>>,
+
+    #[serde(rename = "gidMappings")]
+    pub gid_mappings: Option<Vec<LinuxIdMapping>>,
+
+    #[serde(rename = "resources")]
+    pub resources: Option<Resources>,
+
+    #[serde(rename = "devices")]
+    pub devices: Option<Vec<spec::Device>>,
+
+    #[serde(rename = "maskedPaths")]
+    pub masked_paths: Option<Vec<String>>,
+
+    #[serde(rename = "readonlyPaths")]
+    pub readonly_paths: Option<Vec<String>>,
+
+    #[serde(rename = "sysctl")]
+    pub sysctl: Option<BTreeMap<String, String>>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Resources {
+    #[serde(rename = "memory")]
+    pub memory: Option<MemoryResources>,
+
+    #[serde(rename = "cpu")]
+    pub cpu: Option<CpuResources>,
+
+    #[serde(rename = "pids")]
+    pub pids: Option<PidsResources>,
+
+    #[serde(rename = "devices")]
+    pub devices: Option<Vec<spec::DeviceRule>>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemoryResources {
+    #[serde(rename = "limit")]
+    pub limit: Option<i64>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CpuResources {
+    #[serde(rename = "quota")]
+    pub quota: Option<i64>,
+
+    #[serde(rename = "period")]
+    pub period: Option<u64>,
+
+    #[serde(flatten)]
+    pub unsupported: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PidsResources {
+    #[serde(rename = "limit")]
+    pub limit: i64,
+}
+
+// reads <bundle>/config.json and translates it into a container spec
+// relative paths in the config are resolved against the bundle, which therefore has to be absolute
+pub fn load_bundle(bundle: &Path) -> Result<ContainerSpec, ContainerError> {
+    let content = fs::read_to_string(bundle.join(CONFIG_FILE))
+        .map_err(WrapError::wrapper("reading config.json"))
+        .map_err(ContainerError::wrap)?;
+    let spec: Spec = serde_json::from_str(&content).map_err(ContainerError::wrap)?;
+
+    spec.into_container_spec(bundle)
+}
+
+impl Spec {
+    pub fn into_container_spec(self, bundle: &Path) -> Result<ContainerSpec, ContainerError> {
+        self.warn_unsupported();
+
+        let root = self.root.ok_or_else(|| invalid("root is required"))?;
+        let process = self.process.ok_or_else(|| invalid("process is required"))?;
+        if process.args.is_empty() {
+            return Err(invalid("process.args must not be empty"));
+        }
+
+        let linux = self.linux.unwrap_or(Linux {
+            namespaces: vec![],
+            uid_mappings: None,
+            gid_mappings: None,
+            resources: None,
+            devices: None,
+            masked_paths: None,
+            readonly_paths: None,
+            sysctl: None,
+            unsupported: BTreeMap::new(),
+        });
+        for namespace in &linux.namespaces {
+            if !NAMESPACE_TYPES.contains(&namespace.ns_type.as_str()) {
+                return Err(invalid(&format!("unsupported namespace type {}", namespace.ns_type)));
+            }
+        }
+
+        let resources = linux.resources;
+        let memory = resources.as_ref().and_then(|r| r.memory.as_ref()).and_then(|m| m.limit);
+        let cpu = resources.as_ref().and_then(|r| r.cpu.as_ref());
+        let device_rules = resources.as_ref().and_then(|r| r.devices.clone());
+
+        // the runtime has to provide the default devices, the config only lists additional ones
+        let mut devices = devices::default_devices();
+        for device in linux.devices.into_iter().flatten() {
+            devices.retain(|d| d.path != device.path);
+            devices.push(device);
+        }
+
+        let mounts = self
+            .mounts
+            .into_iter()
+            .map(|m| m.into_spec_mount(bundle))
+            .collect();
+
+        Ok(ContainerSpec {
+            rootfs: spec::Rootfs::Path(bundle.join(root.path).to_string_lossy().to_string()),
+            rootfs_readonly: root.readonly,
+            work_dir: Some(process.cwd),
+            cmd: process.args,
+            env: process.env,
+            user: Some(spec::User {
+                uid: process.user.uid,
+                gid: process.user.gid,
+                additional_gids: process.user.additional_gids,
+            }),
+            capabilities: process.capabilities.and_then(Capabilities::into_list),
+            hostname: self.hostname,
+            domainname: self.domainname,
+            terminal: process.terminal,
+            init: None,
+            cpu: None,
+            // a negative limit means unlimited
+            memory: memory.and_then(|limit| u64::try_from(limit).ok()),
+            cpu_quota: cpu.and_then(|c| c.quota),
+            cpu_period: cpu.and_then(|c| c.period),
+            pids: resources.as_ref().and_then(|r| r.pids.as_ref()).map(|p| p.limit),
+            namespaces: Some(linux.namespaces),
+            uid_mappings: linux.uid_mappings,
+            gid_mappings: linux.gid_mappings,
+            mounts,
+            devices: Some(devices),
+            device_rules,
+            masked_paths: linux.masked_paths,
+            readonly_paths: linux.readonly_paths,
+            sysctl: linux.sysctl,
+            dns: None,
+            extra_hosts: None,
+            log: None,
+            annotations: self.annotations,
+            hooks: self.hooks,
+        })
+    }
+
+    fn warn_unsupported(&self) {
+        if !self.oci_version.starts_with("1.") {
+            log::warn!("config.json is for OCI runtime spec {}, expected 1.x", self.oci_version);
+        }
+
+        let mut unsupported = vec![("", &self.unsupported)];
+        if let Some(root) = &self.root {
+            unsupported.push(("root.", &root.unsupported));
+        }
+        if let Some(process) = &self.process {
+            unsupported.push(("process.", &process.unsupported));
+            unsupported.push(("process.user.", &process.user.unsupported));
+        }
+        if let Some(linux) = &self.linux {
+            unsupported.push(("linux.", &linux.unsupported));
+            if let Some(resources) = &linux.resources {
+                unsupported.push(("linux.resources.", &resources.unsupported));
+                if let Some(memory) = &resources.memory {
+                    unsupported.push(("linux.resources.memory.", &memory.unsupported));
+                }
+                if let Some(cpu) = &resources.cpu {
+                    unsupported.push(("linux.resources.cpu.", &cpu.unsupported));
+                }
+            }
+        }
+
+        for (prefix, fields) in unsupported {
+            for field in fields.keys() {
+                log::warn!("ignoring unsupported config field {}{}", prefix, field);
+            }
+        }
+    }
+}
+
+impl Mount {
+    fn into_spec_mount(self, bundle: &Path) -> spec::Mount {
+        let bind = self.fs_type.as_deref() == Some("bind")
+            || self.options.iter().any(|o| o == "bind" || o == "rbind");
+
+        // the source of a bind mount may be relative to the bundle
+        let source = match self.source {
+            Some(source) if bind && Path::new(&source).is_relative() => {
+                bundle.join(source).to_string_lossy().to_string()
+            }
+            Some(source) => source,
+            None => "none".to_string(),
+        };
+
+        spec::Mount {
+            destination: self.destination,
+            fs_type: self.fs_type.unwrap_or_else(|| "none".to_string()),
+            source,
+            rbind: None,
+            options: self.options,
+            uid_mappings: self.uid_mappings,
+            gid_mappings: self.gid_mappings,
+        }
+    }
+}
+
+fn invalid(msg: &str) -> ContainerError {
+    ContainerError::wrap(io::Error::other(format!("invalid config.json: {}", msg)))
+}
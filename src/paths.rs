@@ -0,0 +1,111 @@
+use crate::spec::ContainerSpec;
+use nix::errno::Errno;
+use nix::mount::{mount, MsFlags};
+use nix::sys::statvfs::{statvfs, FsFlags};
+use std::path::Path;
+use std::{fs, io};
+
+// what runc's default spec hides, kernel memory, key material and hardware details
+const DEFAULT_MASKED_PATHS: [&str; 10] = [
+    "/proc/acpi",
+    "/proc/asound",
+    "/proc/kcore",
+    "/proc/keys",
+    "/proc/latency_stats",
+    "/proc/timer_list",
+    "/proc/timer_stats",
+    "/proc/sched_debug",
+    "/proc/scsi",
+    "/sys/firmware",
+];
+
+// kernel tunables that are not namespaced, writing them would change the host
+const DEFAULT_READONLY_PATHS: [&str; 5] = [
+    "/proc/bus",
+    "/proc/fs",
+    "/proc/irq",
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+];
+
+// the flags a remount has to keep, the kernel refuses to clear them on mounts locked by a user namespace
+const LOCKED_FLAGS: [(FsFlags, MsFlags); 6] = [
+    (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+    (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+    (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+    (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+    (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+    (FsFlags::ST_RELATIME, MsFlags::MS_RELATIME),
+];
+
+pub fn default_masked_paths() -> Vec<String> {
+    DEFAULT_MASKED_PATHS.iter().map(|path| path.to_string()).collect()
+}
+
+pub fn default_readonly_paths() -> Vec<String> {
+    DEFAULT_READONLY_PATHS.iter().map(|path| path.to_string()).collect()
+}
+
+// masks and write protects paths of the container, must be called after pivoting once /proc, /sys and /dev/null exist
+// read-only paths come first, so a masked path below one of them is still masked
+// paths that do not exist in the container are skipped, not every kernel has all of them
+pub fn protect(spec: &ContainerSpec) -> io::Result<()> {
+    let readonly_paths = spec.readonly_paths.clone().unwrap_or_else(default_readonly_paths);
+    for path in &readonly_paths {
+        make_readonly(Path::new(path)).map_err(|e| io::Error::other(format!("making {} read-only: {}", path, e)))?;
+    }
+
+    let masked_paths = spec.masked_paths.clone().unwrap_or_else(default_masked_paths);
+    for path in &masked_paths {
+        mask(Path::new(path)).map_err(|e| io::Error::other(format!("masking {}: {}", path, e)))?;
+    }
+
+    Ok(())
+}
+
+// directories are covered by an empty read-only tmpfs, files by /dev/null
+fn mask(path: &Path) -> io::Result<()> {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    if meta.is_dir() {
+        mount(Some("tmpfs"), path, Some("tmpfs"), MsFlags::MS_RDONLY, None::<&str>)?;
+    } else {
+        mount(Some("/dev/null"), path, None::<&str>, MsFlags::MS_BIND, None::<&str>)?;
+    }
+
+    Ok(())
+}
+
+// a bind mount of the path onto itself can be remounted read-only without affecting the rest of its filesystem
+fn make_readonly(path: &Path) -> io::Result<()> {
+    match mount(Some(path), path, None::<&str>, MsFlags::MS_BIND | MsFlags::MS_REC, None::<&str>) {
+        Ok(()) => (),
+        Err(Errno::ENOENT) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+
+    remount_readonly(path)
+}
+
+// only affects the mount at path, the mounts below it keep their flags
+pub fn remount_readonly(path: &Path) -> io::Result<()> {
+    remount(path, MsFlags::MS_RDONLY)
+}
+
+// replaces the flags of the mount at path, the mounts below it keep theirs
+pub fn remount(path: &Path, flags: MsFlags) -> io::Result<()> {
+    let current = statvfs(path)?.flags();
+    let mut flags = flags | MsFlags::MS_BIND | MsFlags::MS_REMOUNT;
+    for (fs_flag, ms_flag) in LOCKED_FLAGS {
+        if current.contains(fs_flag) {
+            flags.insert(ms_flag);
+        }
+    }
+    mount(None::<&str>, path, None::<&str>, flags, None::<&str>)?;
+
+    Ok(())
+}
@@ -1,43 +1,130 @@
-use crate::cli::ReExecCommands;
+use crate::cli::{LogFormat, ReExecCommands};
+use crate::context::FrogContext;
+use nix::fcntl::OFlag;
 use nix::libc;
 use nix::sched::{clone, CloneFlags};
-use nix::unistd::{execv, Pid};
+use nix::unistd::{execv, pipe2, read, write, Pid};
 use std::ffi::CString;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
 
-pub fn run(command: ReExecCommands) -> nix::Result<Pid> {
+// the sync pipe is passed to the re-exec'd process at this fd
+pub const SYNC_FD: RawFd = 3;
+
+// file descriptors the re-exec'd process uses as its stdio, unset ones are inherited from the parent
+#[derive(Default)]
+pub struct Stdio {
+    pub stdin: Option<OwnedFd>,
+    pub stdout: Option<OwnedFd>,
+    pub stderr: Option<OwnedFd>,
+    pub sync: Option<OwnedFd>,
+}
+
+pub struct ReExecChild {
+    pub pid: Pid,
+    ready: OwnedFd,
+}
+
+impl ReExecChild {
+    // lets the child continue with the re-exec once the parent is done preparing it (e.g. moving it into a cgroup)
+    pub fn resume(self) -> nix::Result<()> {
+        write(&self.ready, &[0])?;
+        Ok(())
+    }
+}
+
+// clones a child in the given new namespaces, which re-executes frogc with the command once resumed
+pub fn run(context: &FrogContext, command: ReExecCommands, flags: CloneFlags, stdio: Stdio) -> nix::Result<ReExecChild> {
     let (cmd, args) = match command {
-        ReExecCommands::Start { container_id } => {
-            ("start".to_string(), vec![container_id])
+        ReExecCommands::Start { sync_fd, container_id } => {
+            let mut args = vec![];
+            if let Some(fd) = sync_fd {
+                args.extend(["--sync-fd".to_string(), fd.to_string()]);
+            }
+            args.push(container_id);
+            ("start".to_string(), args)
         },
     };
 
-    let flags = CloneFlags::CLONE_NEWNS
-        | CloneFlags::CLONE_NEWPID
-        | CloneFlags::CLONE_NEWUTS
-        | CloneFlags::CLONE_NEWIPC;
+    // the re-exec'd process has to use the same run dir as we do
+    // and log where we do, runtimes driving us read the errors of the container setup from the log
+    let mut global_args = vec!["--run-dir".to_string(), context.run_dir_base.to_string_lossy().to_string()];
+    if let Some(log) = &context.log {
+        global_args.extend(["--log".to_string(), log.clone()]);
+    }
+    if let LogFormat::Json = context.log_format {
+        global_args.extend(["--log-format".to_string(), "json".to_string()]);
+    }
+    if context.debug {
+        global_args.push("--debug".to_string());
+    }
+
+    // the child blocks on this pipe until the parent calls resume
+    // both ends are close-on-exec so they do not leak into the re-exec'd process
+    let (ready_read, ready_write) = pipe2(OFlag::O_CLOEXEC)?;
 
     const STACK_SIZE: usize = 1024 * 1024;
     let mut stack = vec![0u8; STACK_SIZE];
 
-    unsafe {
+    let parent_end = ready_write.as_raw_fd();
+    let user_namespace = flags.contains(CloneFlags::CLONE_NEWUSER);
+    let pid = unsafe {
         clone(
-            Box::new(|| child_main(cmd.clone(), args.clone())),
+            Box::new(|| child_main(&ready_read, parent_end, user_namespace, &stdio, global_args.clone(), cmd.clone(), args.clone())),
             &mut stack,
             flags,
             Some(libc::SIGCHLD),
         )
-    }
+    }?;
+
+    Ok(ReExecChild {
+        pid,
+        ready: ready_write,
+    })
 }
 
-unsafe fn child_main(reexec_command: String, args: Vec<String>) -> isize {
+fn child_main(
+    ready: &OwnedFd,
+    parent_end: RawFd,
+    user_namespace: bool,
+    stdio: &Stdio,
+    global_args: Vec<String>,
+    reexec_command: String,
+    args: Vec<String>,
+) -> isize {
+    // close our copy of the write end, otherwise we would never see EOF if the parent dies
+    unsafe { libc::close(parent_end) };
+
+    let mut buf = [0u8; 1];
+    if read(ready, &mut buf).unwrap_or(0) == 0 {
+        // the parent went away without resuming us
+        return 1;
+    }
+
+    // we still have the host's ids, which the parent usually did not map into the user namespace
+    // without switching to the namespace's root the re-exec would drop all our capabilities
+    if user_namespace
+        && unsafe { libc::setresgid(0, 0, 0) < 0 || libc::setresuid(0, 0, 0) < 0 }
+    {
+        return 1;
+    }
+
+    // dup2 clears close-on-exec on the target, so the stdio survives the re-exec while the originals do not
+    for (fd, target) in [(&stdio.stdin, 0), (&stdio.stdout, 1), (&stdio.stderr, 2), (&stdio.sync, SYNC_FD)] {
+        if let Some(fd) = fd
+            && unsafe { libc::dup2(fd.as_raw_fd(), target) } < 0
+        {
+            return 1;
+        }
+    }
+
     let exe = CString::from(c"/proc/self/exe");
 
     let mut cargs = vec![exe.clone()];
+    cargs.extend(global_args.iter().map(|arg| CString::new(arg.clone()).unwrap()));
     cargs.push(CString::new("re-exec").unwrap());
     cargs.push(CString::new(reexec_command).unwrap());
     cargs.extend(args.iter().map(|arg| CString::new(arg.clone()).unwrap()));
 
-    execv(&exe, &cargs).unwrap();
-
-    unreachable!()
+    let Err(e) = execv(&exe, &cargs);
+    panic!("re-exec failed: {}", e)
 }
@@ -0,0 +1,122 @@
+use crate::context::FrogContext;
+use crate::namespaces;
+use crate::spec::{ContainerSpec, ContainerState, Overlay, Rootfs};
+use crate::syscall::{self, move_mount, open_tree, MountAttr, MountAttrFlags};
+use nix::errno::Errno;
+use nix::fcntl::{AtFlags, AT_FDCWD};
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+use std::os::fd::AsFd;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+// the directories in the container run dir, lower/<n> only exists with idmapped lower dirs
+const MERGED_DIR: &str = "rootfs";
+const LOWER_DIR: &str = "lower";
+
+// the directory the container pivots into
+pub fn path(state: &ContainerState) -> io::Result<&str> {
+    match (&state.spec.rootfs, &state.rootfs_mount) {
+        (Rootfs::Path(path), _) => Ok(path),
+        (Rootfs::Overlay(_), Some(path)) => Ok(path),
+        // create records the mount point, a state without one was not written by it
+        (Rootfs::Overlay(_), None) => Err(io::Error::other("the state has no mount point for the overlay rootfs")),
+    }
+}
+
+// assembles the rootfs of a new container, returns where it is mounted if it is not just a path
+// the mount lives in the host's mount namespace, so it outlives the container process until delete
+pub fn mount_rootfs(context: &FrogContext, container_id: &str, spec: &ContainerSpec) -> io::Result<Option<PathBuf>> {
+    let Rootfs::Overlay(overlay) = &spec.rootfs else {
+        return Ok(None);
+    };
+
+    let run_dir = context.container_run_dir(container_id);
+    let result = mount_overlay(&run_dir, spec, overlay);
+    // a failed mount must not leave idmapped lower dirs behind, the run dir is removed next
+    if result.is_err()
+        && let Err(e) = unmount_rootfs(context, container_id)
+    {
+        log::warn!("cleaning up rootfs of {}: {}", container_id, e);
+    }
+
+    result.map(Some)
+}
+
+// must be called before removing the run dir, removing it recursively would otherwise delete the layers' contents
+// MNT_DETACH takes the mounts out of the tree even if something still uses them
+pub fn unmount_rootfs(context: &FrogContext, container_id: &str) -> io::Result<()> {
+    let run_dir = context.container_run_dir(container_id);
+    unmount(&run_dir.join(MERGED_DIR))?;
+
+    let lower = run_dir.join(LOWER_DIR);
+    let entries = match fs::read_dir(&lower) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        unmount(&entry?.path())?;
+    }
+
+    Ok(())
+}
+
+fn mount_overlay(run_dir: &Path, spec: &ContainerSpec, overlay: &Overlay) -> io::Result<PathBuf> {
+    let lower_dirs = if overlay.idmap_lower_dirs == Some(true) {
+        idmap_lower_dirs(run_dir, spec, &overlay.lower_dirs)?
+    } else {
+        overlay.lower_dirs.iter().map(PathBuf::from).collect()
+    };
+
+    let lower_dirs: Vec<String> = lower_dirs.iter().map(|dir| dir.to_string_lossy().to_string()).collect();
+    let mut options = vec![format!("lowerdir={}", lower_dirs.join(":"))];
+    if let Some(upper_dir) = &overlay.upper_dir {
+        options.push(format!("upperdir={}", upper_dir));
+    }
+    if let Some(work_dir) = &overlay.work_dir {
+        options.push(format!("workdir={}", work_dir));
+    }
+    if overlay.volatile == Some(true) {
+        options.push("volatile".to_string());
+    }
+
+    let merged = run_dir.join(MERGED_DIR);
+    fs::create_dir_all(&merged)?;
+    mount(Some("overlay"), &merged, Some("overlay"), MsFlags::empty(), Some(options.join(",").as_str()))?;
+
+    Ok(merged)
+}
+
+// overlayfs does not take idmaps itself, but accepts idmapped mounts as layers
+// each lower dir gets an idmapped bind mount in the run dir, using a user namespace with the container's mappings
+fn idmap_lower_dirs(run_dir: &Path, spec: &ContainerSpec, lower_dirs: &[String]) -> io::Result<Vec<PathBuf>> {
+    let userns = namespaces::new_user_namespace(spec)?;
+    let attr = MountAttr {
+        attr_set: MountAttrFlags::MOUNT_ATTR_IDMAP,
+        attr_clr: MountAttrFlags::empty(),
+        propagation: MsFlags::empty(),
+        userns_fd: userns.as_fd(),
+    };
+
+    let mut mapped = vec![];
+    for (i, lower_dir) in lower_dirs.iter().enumerate() {
+        let target = run_dir.join(LOWER_DIR).join(i.to_string());
+        fs::create_dir_all(&target)?;
+
+        let tree = open_tree(AT_FDCWD, lower_dir.as_str(), false)?;
+        syscall::mount_setattr(Some(tree.as_fd()), "", AtFlags::AT_EMPTY_PATH, &attr)?;
+        move_mount(tree.as_fd(), &target)?;
+
+        mapped.push(target);
+    }
+
+    Ok(mapped)
+}
+
+fn unmount(path: &Path) -> io::Result<()> {
+    match umount2(path, MntFlags::MNT_DETACH) {
+        // not mounted, or never created
+        Ok(()) | Err(Errno::EINVAL | Errno::ENOENT) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
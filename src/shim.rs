@@ -0,0 +1,603 @@
+use crate::commands::start;
+use crate::console;
+use crate::context::FrogContext;
+use crate::errors::{ContainerError, WrapError};
+use crate::logging;
+use crate::logging::Logger;
+use crate::reexec::Stdio;
+use crate::spec::ContainerState;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::prctl::set_child_subreaper;
+use nix::sys::signal::{kill, SigSet, Signal};
+use nix::sys::signalfd::{SfdFlags, SignalFd};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{dup2_stderr, dup2_stdin, dup2_stdout, fork, pipe2, setsid, ForkResult, Pid};
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::fd::AsFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{fs, io, process};
+
+const CONTROL_SOCKET: &str = "control.sock";
+const CONSOLE_SOCKET: &str = "console.sock";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const ATTACH_WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// once the container exited we keep reading its output until nothing arrives for this long
+const DRAIN_TIMEOUT_MS: u16 = 500;
+
+pub fn control_socket_path(context: &FrogContext, container_id: &str) -> PathBuf {
+    context.container_run_dir(container_id).join(CONTROL_SOCKET)
+}
+
+// the socket the container sends its pty master to when the spec enables a terminal and create got no --console-socket
+pub fn console_socket_path(context: &FrogContext, container_id: &str) -> PathBuf {
+    context.container_run_dir(container_id).join(CONSOLE_SOCKET)
+}
+
+// requests are sent as a single JSON line over the control socket, the shim answers with a single JSON line
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Request {
+    #[serde(rename = "state")]
+    State,
+
+    #[serde(rename = "kill")]
+    Kill {
+        #[serde(rename = "signal")]
+        signal: i32,
+    },
+
+    #[serde(rename = "resize")]
+    Resize {
+        #[serde(rename = "rows")]
+        rows: u16,
+
+        #[serde(rename = "cols")]
+        cols: u16,
+    },
+
+    // after a successful response the connection carries the container's output as frames (see write_frame)
+    // unless read only, everything the client sends afterwards is written to the container's stdin
+    #[serde(rename = "attach")]
+    Attach {
+        #[serde(rename = "readOnly")]
+        read_only: bool,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Response {
+    #[serde(rename = "error", skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    #[serde(rename = "state", skip_serializing_if = "Option::is_none")]
+    pub state: Option<ContainerState>,
+}
+
+impl Response {
+    fn error(msg: impl ToString) -> Self {
+        Self {
+            error: Some(msg.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn into_result(self) -> Result<Self, ContainerError> {
+        match self.error {
+            Some(e) => Err(ContainerError::wrap(io::Error::other(e))),
+            None => Ok(self),
+        }
+    }
+}
+
+// output frames start with the stream the data was written to and the length of the data
+pub const STDOUT: u8 = 1;
+pub const STDERR: u8 = 2;
+pub const FRAME_HEADER_LEN: usize = 5;
+
+// sends a request to the container's shim
+// returns None if no shim is listening, e.g. because the container is run attached by `frogc run`
+pub fn request(context: &FrogContext, container_id: &str, request: &Request) -> io::Result<Option<Response>> {
+    Ok(connect(context, container_id, request)?.map(|(response, _)| response))
+}
+
+// like request, but hands back the connection for requests that keep using it
+pub fn connect(
+    context: &FrogContext,
+    container_id: &str,
+    request: &Request,
+) -> io::Result<Option<(Response, UnixStream)>> {
+    let stream = match UnixStream::connect(control_socket_path(context, container_id)) {
+        Ok(stream) => stream,
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused) => {
+            return Ok(None);
+        }
+        Err(e) => return Err(e),
+    };
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    (&stream).write_all(line.as_bytes())?;
+
+    // read byte by byte, a buffered reader could swallow output following the response
+    let mut line = vec![];
+    let mut byte = [0u8];
+    while (&stream).read(&mut byte)? == 1 && byte[0] != b'\n' {
+        line.push(byte[0]);
+    }
+
+    stream.set_read_timeout(None)?;
+    Ok(Some((serde_json::from_slice(&line)?, stream)))
+}
+
+fn write_frame(mut stream: &UnixStream, stream_id: u8, data: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LEN + data.len());
+    frame.push(stream_id);
+    frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    frame.extend_from_slice(data);
+    stream.write_all(&frame)
+}
+
+// splits complete frames off the front of buf, returns the stream and data of each
+pub fn read_frames(buf: &mut Vec<u8>) -> Vec<(u8, Vec<u8>)> {
+    let mut frames = vec![];
+    while buf.len() >= FRAME_HEADER_LEN {
+        let len = u32::from_be_bytes([buf[1], buf[2], buf[3], buf[4]]) as usize;
+        if buf.len() < FRAME_HEADER_LEN + len {
+            break;
+        }
+
+        let rest = buf.split_off(FRAME_HEADER_LEN + len);
+        let frame = std::mem::replace(buf, rest);
+        frames.push((frame[0], frame[FRAME_HEADER_LEN..].to_vec()));
+    }
+
+    frames
+}
+
+// runs the container from a shim process that outlives the CLI
+// we only wait until the shim reports whether the container could be started
+pub fn start_detached(context: &FrogContext, container_id: &str) -> Result<(), ContainerError> {
+    let (report_read, report_write) = pipe2(OFlag::O_CLOEXEC).map_err(ContainerError::wrap)?;
+
+    match unsafe { fork() }.map_err(ContainerError::wrap)? {
+        ForkResult::Child => {
+            drop(report_read);
+
+            // a new session detaches us from the caller's terminal, forking again makes sure we never acquire one
+            let _ = setsid();
+            if let Ok(ForkResult::Parent { .. }) = unsafe { fork() } {
+                process::exit(0);
+            }
+
+            // holding on to the caller's stdio would keep e.g. a pipe reading our output open for the container's lifetime
+            if let Ok(dev_null) = OpenOptions::new().read(true).write(true).open("/dev/null") {
+                let _ = dup2_stdin(&dev_null);
+                let _ = dup2_stdout(&dev_null);
+                let _ = dup2_stderr(&dev_null);
+            }
+
+            let mut report = File::from(report_write);
+            let shim = match Shim::spawn(context, container_id, false) {
+                Ok(shim) => shim,
+                Err(e) => {
+                    let _ = write!(report, "{}", e);
+                    process::exit(1);
+                }
+            };
+            let _ = report.write_all(b"ok");
+            drop(report);
+
+            match shim.run() {
+                Ok(_) => process::exit(0),
+                Err(e) => {
+                    log::error!("shim for container {}: {}", container_id, e);
+                    process::exit(1);
+                }
+            }
+        }
+        ForkResult::Parent { child } => {
+            drop(report_write);
+            waitpid(child, None).map_err(ContainerError::wrap)?;
+
+            let mut report = String::new();
+            File::from(report_read)
+                .read_to_string(&mut report)
+                .map_err(ContainerError::wrap)?;
+
+            match report.as_str() {
+                "ok" => Ok(()),
+                "" => Err(ContainerError::wrap(io::Error::other(
+                    "shim exited before starting the container",
+                ))),
+                e => Err(ContainerError::wrap(io::Error::other(e.to_string()))),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Source {
+    ChildExited,
+    Control,
+    Stdout,
+    Stderr,
+    Console,
+    Input,
+}
+
+struct Attacher {
+    stream: UnixStream,
+    read_only: bool,
+}
+
+// the parent of a container process: it owns the container's stdio, writes its output to the log,
+// reaps it, records its exit status and answers requests on the control socket while the container runs
+pub struct Shim {
+    context: FrogContext,
+    container_id: String,
+    child_pid: Pid,
+    control: UnixListener,
+    sigchld: SignalFd,
+    stdin: Option<File>,
+    stdout: Option<File>,
+    stderr: Option<File>,
+    console: Option<File>,
+    logger: Option<Logger>,
+    echo: bool,
+    attachers: Vec<Attacher>,
+    exit_status: Option<WaitStatus>,
+}
+
+impl Shim {
+    // starts the container, with echo set its output is also copied to our own stdout and stderr
+    pub fn spawn(context: &FrogContext, container_id: &str, echo: bool) -> Result<Self, ContainerError> {
+        // processes orphaned inside the container's process tree get reparented to us instead of the host's init
+        set_child_subreaper(true)
+            .map_err(WrapError::wrapper("becoming child subreaper"))
+            .map_err(ContainerError::wrap)?;
+
+        let state = context
+            .read_state(container_id)
+            .map_err(ContainerError::wrap)?;
+        let terminal = state.spec.terminal.unwrap_or(false);
+
+        let logger = match &state.spec.log {
+            Some(config) => Some(
+                Logger::open(logging::log_path(context, container_id, config), config)
+                    .map_err(WrapError::wrapper("opening log file"))
+                    .map_err(ContainerError::wrap)?,
+            ),
+            None => None,
+        };
+
+        let control = bind(control_socket_path(context, container_id))
+            .map_err(WrapError::wrapper("binding control socket"))
+            .map_err(ContainerError::wrap)?;
+
+        // with a terminal the pty replaces the stdio, we own it unless create was given a console socket
+        let console_listener = if terminal && state.console_socket.is_none() {
+            Some(
+                bind(console_socket_path(context, container_id))
+                    .map_err(WrapError::wrapper("binding console socket"))
+                    .map_err(ContainerError::wrap)?,
+            )
+        } else {
+            None
+        };
+
+        let mut stdio = Stdio::default();
+        let (mut stdin, mut stdout, mut stderr) = (None, None, None);
+        if !terminal {
+            // in the foreground the container reads the caller's stdin directly
+            if !echo {
+                let (read, write) = pipe2(OFlag::O_CLOEXEC).map_err(ContainerError::wrap)?;
+                stdio.stdin = Some(read);
+                stdin = Some(File::from(write));
+            }
+
+            let (read, write) = pipe2(OFlag::O_CLOEXEC).map_err(ContainerError::wrap)?;
+            stdio.stdout = Some(write);
+            stdout = Some(File::from(read));
+
+            let (read, write) = pipe2(OFlag::O_CLOEXEC).map_err(ContainerError::wrap)?;
+            stdio.stderr = Some(write);
+            stderr = Some(File::from(read));
+        }
+
+        let child_pid = start::spawn(context, container_id, stdio)?;
+
+        // SIGCHLD is only handled through the signalfd, an exit before we blocked it is picked up by the first reap
+        let mut mask = SigSet::empty();
+        mask.add(Signal::SIGCHLD);
+        mask.thread_block().map_err(ContainerError::wrap)?;
+        let sigchld = SignalFd::with_flags(&mask, SfdFlags::SFD_CLOEXEC | SfdFlags::SFD_NONBLOCK)
+            .map_err(ContainerError::wrap)?;
+
+        let console = match console_listener {
+            Some(listener) => {
+                let master = console::accept(&listener, child_pid)
+                    .map_err(WrapError::wrapper("receiving pty master"))
+                    .map_err(ContainerError::wrap)?;
+                let _ = fs::remove_file(console_socket_path(context, container_id));
+                master.map(File::from)
+            }
+            None => None,
+        };
+
+        let mut shim = Self {
+            context: context.clone(),
+            container_id: container_id.to_string(),
+            child_pid,
+            control,
+            sigchld,
+            stdin,
+            stdout,
+            stderr,
+            console,
+            logger,
+            echo,
+            attachers: vec![],
+            exit_status: None,
+        };
+        shim.reap();
+
+        Ok(shim)
+    }
+
+    // serves the container until it exited and its output is drained, then records its exit status
+    pub fn run(mut self) -> Result<WaitStatus, ContainerError> {
+        loop {
+            let mut sources = vec![Source::ChildExited, Source::Control];
+            let mut fds = vec![
+                PollFd::new(self.sigchld.as_fd(), PollFlags::POLLIN),
+                PollFd::new(self.control.as_fd(), PollFlags::POLLIN),
+            ];
+            for (source, file) in [
+                (Source::Stdout, &self.stdout),
+                (Source::Stderr, &self.stderr),
+                (Source::Console, &self.console),
+            ] {
+                if let Some(file) = file {
+                    sources.push(source);
+                    fds.push(PollFd::new(file.as_fd(), PollFlags::POLLIN));
+                }
+            }
+            if let Some(attacher) = self.attachers.iter().find(|a| !a.read_only) {
+                sources.push(Source::Input);
+                fds.push(PollFd::new(attacher.stream.as_fd(), PollFlags::POLLIN));
+            }
+
+            let timeout = if self.exit_status.is_some() { PollTimeout::from(DRAIN_TIMEOUT_MS) } else { PollTimeout::NONE };
+            let ready = match poll(&mut fds, timeout) {
+                Err(Errno::EINTR) => continue,
+                r => r.map_err(ContainerError::wrap)?,
+            };
+            if ready == 0 {
+                break;
+            }
+
+            let ready: Vec<Source> = sources
+                .into_iter()
+                .zip(fds.iter())
+                .filter(|(_, fd)| fd.any().unwrap_or(false))
+                .map(|(source, _)| source)
+                .collect();
+            drop(fds);
+
+            for source in ready {
+                match source {
+                    Source::ChildExited => {
+                        while self.sigchld.read_signal().ok().flatten().is_some() {}
+                        self.reap();
+                    }
+                    Source::Control => self.accept_request(),
+                    Source::Input => self.forward_input(),
+                    Source::Stdout | Source::Stderr | Source::Console => self.forward_output(source),
+                }
+            }
+
+            let output_open = self.stdout.is_some() || self.stderr.is_some() || self.console.is_some();
+            if self.exit_status.is_some() && !output_open {
+                break;
+            }
+        }
+
+        let _ = fs::remove_file(control_socket_path(&self.context, &self.container_id));
+        drop(self.stdin.take());
+
+        // exit_status is always set here, the loop only ends after the container exited
+        let status = self.exit_status.unwrap_or(WaitStatus::StillAlive);
+        start::record_exit(&self.context, &self.container_id, status)?;
+
+        Ok(status)
+    }
+
+    // as a subreaper we have to reap every process that gets reparented to us, not only the container process
+    fn reap(&mut self) {
+        loop {
+            match waitpid(None, Some(WaitPidFlag::WNOHANG)) {
+                Ok(WaitStatus::StillAlive) | Err(_) => return,
+                Ok(status) if status.pid() == Some(self.child_pid) => self.exit_status = Some(status),
+                Ok(_) => (),
+            }
+        }
+    }
+
+    fn forward_output(&mut self, source: Source) {
+        let (file, stream) = match source {
+            Source::Stdout => (&mut self.stdout, "stdout"),
+            Source::Stderr => (&mut self.stderr, "stderr"),
+            _ => (&mut self.console, "stdout"),
+        };
+        let stream_id = if stream == "stderr" { STDERR } else { STDOUT };
+        let Some(reader) = file else {
+            return;
+        };
+
+        let mut buf = [0u8; 64 * 1024];
+        // reading a pty master fails with EIO once every process in the container closed the slave
+        let n = reader.read(&mut buf).unwrap_or(0);
+        if n == 0 {
+            *file = None;
+            if let Some(logger) = &mut self.logger
+                && let Err(e) = logger.close(stream)
+            {
+                log::error!("writing log: {}", e);
+            }
+            return;
+        }
+
+        let data = &buf[..n];
+        if let Some(logger) = &mut self.logger
+            && let Err(e) = logger.write(stream, data)
+        {
+            log::error!("writing log: {}", e);
+        }
+
+        if self.echo {
+            let _ = match stream {
+                "stderr" => io::stderr().write_all(data),
+                _ => io::stdout().write_all(data).and_then(|_| io::stdout().flush()),
+            };
+        }
+
+        // an attacher that cannot keep up or went away is dropped rather than holding up the container
+        self.attachers
+            .retain(|attacher| write_frame(&attacher.stream, stream_id, data).is_ok());
+    }
+
+    // copies what the read-write attacher sends to the container's stdin, or its pty
+    fn forward_input(&mut self) {
+        let Some(index) = self.attachers.iter().position(|a| !a.read_only) else {
+            return;
+        };
+
+        let mut buf = [0u8; 4096];
+        let n = (&self.attachers[index].stream).read(&mut buf).unwrap_or(0);
+        if n == 0 {
+            // the client detached
+            self.attachers.remove(index);
+            return;
+        }
+
+        let target = self.console.as_mut().or(self.stdin.as_mut());
+        if let Some(target) = target
+            && let Err(e) = target.write_all(&buf[..n])
+        {
+            log::warn!("writing to container stdin: {}", e);
+        }
+    }
+
+    fn accept_request(&mut self) {
+        let Ok((stream, _)) = self.control.accept() else {
+            return;
+        };
+
+        if let Err(e) = self.handle_request(stream) {
+            log::warn!("handling control request: {}", e);
+        }
+    }
+
+    // clients wait for the response before sending anything else, so the buffered reader cannot read past the request
+    fn handle_request(&mut self, stream: UnixStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line)?;
+
+        let (response, attach) = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Attach { read_only }) => (self.check_attach(read_only), Some(read_only)),
+            Ok(request) => (self.dispatch(request), None),
+            Err(e) => (Response::error(format!("invalid request: {}", e)), None),
+        };
+
+        let mut line = serde_json::to_string(&response)?;
+        line.push('\n');
+        (&stream).write_all(line.as_bytes())?;
+
+        if let Some(read_only) = attach
+            && response.error.is_none()
+        {
+            stream.set_read_timeout(None)?;
+            stream.set_write_timeout(Some(ATTACH_WRITE_TIMEOUT))?;
+            self.attachers.push(Attacher { stream, read_only });
+        }
+
+        Ok(())
+    }
+
+    // any number of clients can watch the output, but only one at a time may type into the container
+    fn check_attach(&self, read_only: bool) -> Response {
+        if self.exit_status.is_some() {
+            return Response::error("container has already exited");
+        }
+        if !read_only && self.attachers.iter().any(|a| !a.read_only) {
+            return Response::error("another client is attached read-write, attach read only instead");
+        }
+
+        Response::default()
+    }
+
+    fn dispatch(&mut self, request: Request) -> Response {
+        match request {
+            Request::State => match self.read_state() {
+                Ok(state) => Response {
+                    state: Some(state),
+                    ..Default::default()
+                },
+                Err(e) => Response::error(e),
+            },
+            Request::Kill { signal } => {
+                if self.exit_status.is_some() {
+                    return Response::error("container has already exited");
+                }
+
+                match Signal::try_from(signal).and_then(|signal| kill(self.child_pid, signal)) {
+                    Ok(_) => Response::default(),
+                    Err(e) => Response::error(e),
+                }
+            }
+            Request::Attach { .. } => Response::error("attach requests are handled separately"),
+            Request::Resize { rows, cols } => {
+                let Some(console) = &self.console else {
+                    return Response::error("container has no terminal");
+                };
+
+                let size = libc::winsize {
+                    ws_row: rows,
+                    ws_col: cols,
+                    ws_xpixel: 0,
+                    ws_ypixel: 0,
+                };
+                match console::set_window_size(console.as_fd(), &size) {
+                    Ok(_) => Response::default(),
+                    Err(e) => Response::error(e),
+                }
+            }
+        }
+    }
+
+    fn read_state(&self) -> io::Result<ContainerState> {
+        let _lock = self.context.lookup_container(&self.container_id).map_err(io::Error::other)?;
+        self.context.read_state(&self.container_id)
+    }
+}
+
+// removes a socket left behind by a previous shim before binding a new one
+fn bind(path: PathBuf) -> io::Result<UnixListener> {
+    match fs::remove_file(&path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => (),
+    }
+
+    UnixListener::bind(path)
+}
@@ -1,24 +1,302 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ContainerSpec {
     #[serde(rename = "rootfs")]
-    pub rootfs: String,
+    pub rootfs: Rootfs,
 
-    #[serde(rename = "workDir")]
+    // mounts the rootfs read-only, only the mounts on top of it stay writable
+    #[serde(rename = "rootfsReadonly", skip_serializing_if = "Option::is_none")]
+    pub rootfs_readonly: Option<bool>,
+
+    #[serde(rename = "workDir", skip_serializing_if = "Option::is_none")]
     pub work_dir: Option<String>,
 
     #[serde(rename = "cmd")]
     pub cmd: Vec<String>,
 
-    #[serde(rename = "cpu")]
+    // the environment of the command, without it the command inherits the environment of frogc
+    #[serde(rename = "env", skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+
+    // the user the command runs as, root if unset
+    #[serde(rename = "user", skip_serializing_if = "Option::is_none")]
+    pub user: Option<User>,
+
+    // the capabilities the command may have, everything else is dropped from all sets including the bounding set
+    // the command keeps all capabilities of root if unset
+    #[serde(rename = "capabilities", skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Vec<String>>,
+
+    // the names in the container's own UTS namespace, both default to the container id
+    #[serde(rename = "hostname", skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+
+    #[serde(rename = "domainname", skip_serializing_if = "Option::is_none")]
+    pub domainname: Option<String>,
+
+    #[serde(rename = "terminal", skip_serializing_if = "Option::is_none")]
+    pub terminal: Option<bool>,
+
+    // run a minimal init as PID 1 that forwards signals to the command and reaps orphaned processes
+    #[serde(rename = "init", skip_serializing_if = "Option::is_none")]
+    pub init: Option<bool>,
+
+    #[serde(rename = "cpu", skip_serializing_if = "Option::is_none")]
     pub cpu: Option<u32>,
 
-    #[serde(rename = "memory")]
+    #[serde(rename = "memory", skip_serializing_if = "Option::is_none")]
     pub memory: Option<u64>,
 
+    // an exact CPU bandwidth limit in microseconds per period, takes precedence over cpu
+    #[serde(rename = "cpuQuota", skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<i64>,
+
+    #[serde(rename = "cpuPeriod", skip_serializing_if = "Option::is_none")]
+    pub cpu_period: Option<u64>,
+
+    // the maximum number of processes in the container, -1 for no limit
+    #[serde(rename = "pids", skip_serializing_if = "Option::is_none")]
+    pub pids: Option<i64>,
+
+    // the namespaces the container gets, mount, pid, uts and ipc if unset
+    #[serde(rename = "namespaces", skip_serializing_if = "Option::is_none")]
+    pub namespaces: Option<Vec<Namespace>>,
+
+    // id mappings of the user namespace, only used if the namespaces include one
+    #[serde(rename = "uidMappings", skip_serializing_if = "Option::is_none")]
+    pub uid_mappings: Option<Vec<LinuxIdMapping>>,
+
+    #[serde(rename = "gidMappings", skip_serializing_if = "Option::is_none")]
+    pub gid_mappings: Option<Vec<LinuxIdMapping>>,
+
     #[serde(rename = "mounts")]
     pub mounts: Vec<Mount>,
+
+    // the device nodes created in the container, null, zero, full, random, urandom and tty if unset
+    #[serde(rename = "devices", skip_serializing_if = "Option::is_none")]
+    pub devices: Option<Vec<Device>>,
+
+    // which devices the container may access, on top of denying everything but its device nodes
+    // when rules overlap, the last matching one wins
+    #[serde(rename = "deviceRules", skip_serializing_if = "Option::is_none")]
+    pub device_rules: Option<Vec<DeviceRule>>,
+
+    // paths hidden from the container, the defaults of paths.rs if unset
+    #[serde(rename = "maskedPaths", skip_serializing_if = "Option::is_none")]
+    pub masked_paths: Option<Vec<String>>,
+
+    // paths the container can only read, the defaults of paths.rs if unset
+    #[serde(rename = "readonlyPaths", skip_serializing_if = "Option::is_none")]
+    pub readonly_paths: Option<Vec<String>>,
+
+    // kernel parameters set in the container, only those of namespaces the container owns, see sysctl.rs
+    #[serde(rename = "sysctl", skip_serializing_if = "Option::is_none")]
+    pub sysctl: Option<BTreeMap<String, String>>,
+
+    // the resolvers written to /etc/resolv.conf, every unset field is taken from the host's resolv.conf
+    #[serde(rename = "dns", skip_serializing_if = "Option::is_none")]
+    pub dns: Option<Dns>,
+
+    // entries added to the generated /etc/hosts
+    #[serde(rename = "extraHosts", skip_serializing_if = "Option::is_none")]
+    pub extra_hosts: Option<Vec<ExtraHost>>,
+
+    #[serde(rename = "log", skip_serializing_if = "Option::is_none")]
+    pub log: Option<LogConfig>,
+
+    #[serde(rename = "annotations", skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<BTreeMap<String, String>>,
+
+    // programs run at points of the container lifecycle, as defined by the OCI runtime spec
+    #[serde(rename = "hooks", skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+}
+
+// either the path of a prepared root filesystem or the layers frogc assembles one from
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum Rootfs {
+    Path(String),
+    Overlay(Overlay),
+}
+
+// an overlayfs mounted in the container run dir when the container is created and unmounted on delete
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Overlay {
+    // the read-only layers, the top-most first like overlayfs's lowerdir
+    #[serde(rename = "lowerDirs")]
+    pub lower_dirs: Vec<String>,
+
+    // where changes are written, without it the rootfs is read-only and needs at least two lower dirs
+    #[serde(rename = "upperDir", skip_serializing_if = "Option::is_none")]
+    pub upper_dir: Option<String>,
+
+    // an empty directory on the same filesystem as upperDir, required with it
+    #[serde(rename = "workDir", skip_serializing_if = "Option::is_none")]
+    pub work_dir: Option<String>,
+
+    // skips syncing the upper dir, which is faster but loses the changes if the host crashes
+    #[serde(rename = "volatile", skip_serializing_if = "Option::is_none")]
+    pub volatile: Option<bool>,
+
+    // maps the owners of the lower dirs through the container's user namespace, so layers owned by
+    // the host's root can be shared by containers with different id mappings
+    #[serde(rename = "idmapLowerDirs", skip_serializing_if = "Option::is_none")]
+    pub idmap_lower_dirs: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct User {
+    #[serde(rename = "uid")]
+    pub uid: u32,
+
+    #[serde(rename = "gid")]
+    pub gid: u32,
+
+    #[serde(rename = "additionalGids", skip_serializing_if = "Option::is_none")]
+    pub additional_gids: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Namespace {
+    // one of mount, pid, uts, ipc, network, user or cgroup
+    #[serde(rename = "type")]
+    pub ns_type: String,
+
+    // joins the namespace at this path instead of creating a new one
+    #[serde(rename = "path", skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Device {
+    #[serde(rename = "path")]
+    pub path: String,
+
+    // c (or u) for a character device, b for a block device, p for a fifo
+    #[serde(rename = "type")]
+    pub dev_type: String,
+
+    // major and minor are required for character and block devices
+    #[serde(rename = "major", skip_serializing_if = "Option::is_none")]
+    pub major: Option<u64>,
+
+    #[serde(rename = "minor", skip_serializing_if = "Option::is_none")]
+    pub minor: Option<u64>,
+
+    // 0666 if unset
+    #[serde(rename = "fileMode", skip_serializing_if = "Option::is_none")]
+    pub file_mode: Option<u32>,
+
+    #[serde(rename = "uid", skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+
+    #[serde(rename = "gid", skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeviceRule {
+    #[serde(rename = "allow")]
+    pub allow: bool,
+
+    // a for all devices, c for character devices, b for block devices, a if unset
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub dev_type: Option<String>,
+
+    // unset or -1 matches any number
+    #[serde(rename = "major", skip_serializing_if = "Option::is_none")]
+    pub major: Option<i64>,
+
+    #[serde(rename = "minor", skip_serializing_if = "Option::is_none")]
+    pub minor: Option<i64>,
+
+    // any combination of r (read), w (write) and m (mknod), rwm if unset
+    #[serde(rename = "access", skip_serializing_if = "Option::is_none")]
+    pub access: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Dns {
+    // IP addresses of the name servers
+    #[serde(rename = "servers", skip_serializing_if = "Option::is_none")]
+    pub servers: Option<Vec<String>>,
+
+    #[serde(rename = "search", skip_serializing_if = "Option::is_none")]
+    pub search: Option<Vec<String>>,
+
+    // resolver options like ndots:2
+    #[serde(rename = "options", skip_serializing_if = "Option::is_none")]
+    pub options: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtraHost {
+    #[serde(rename = "ip")]
+    pub ip: String,
+
+    #[serde(rename = "hostnames")]
+    pub hostnames: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Hooks {
+    // deprecated in favor of createRuntime, runs right before it
+    #[serde(rename = "prestart", skip_serializing_if = "Option::is_none")]
+    pub prestart: Option<Vec<Hook>>,
+
+    // runs on the host once the namespaces exist, before the container's mounts are set up
+    #[serde(rename = "createRuntime", skip_serializing_if = "Option::is_none")]
+    pub create_runtime: Option<Vec<Hook>>,
+
+    // runs in the container's namespaces before pivoting into the rootfs
+    #[serde(rename = "createContainer", skip_serializing_if = "Option::is_none")]
+    pub create_container: Option<Vec<Hook>>,
+
+    // runs in the container right before the command is executed, paths are inside the rootfs
+    #[serde(rename = "startContainer", skip_serializing_if = "Option::is_none")]
+    pub start_container: Option<Vec<Hook>>,
+
+    // runs on the host once the command has been started
+    #[serde(rename = "poststart", skip_serializing_if = "Option::is_none")]
+    pub poststart: Option<Vec<Hook>>,
+
+    // runs on the host once the container has been deleted
+    #[serde(rename = "poststop", skip_serializing_if = "Option::is_none")]
+    pub poststop: Option<Vec<Hook>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Hook {
+    #[serde(rename = "path")]
+    pub path: String,
+
+    // like for execve, the first argument is the name the program sees itself as
+    #[serde(rename = "args", skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+
+    // the whole environment of the hook, it does not inherit ours
+    #[serde(rename = "env", skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+
+    // seconds after which the hook is killed and fails
+    #[serde(rename = "timeout", skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LogConfig {
+    #[serde(rename = "path", skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    #[serde(rename = "maxSize", skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+
+    #[serde(rename = "maxFiles", skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,21 +309,27 @@ pub struct Mount {
 
     #[serde(rename = "source")]
     pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub rbind: Option<bool>,
     #[serde(rename = "options")]
     pub options: Vec<String>,
 
-    #[serde(rename = "uidMappings")]
+    #[serde(rename = "uidMappings", skip_serializing_if = "Option::is_none")]
     pub uid_mappings: Option<Vec<LinuxIdMapping>>,
 
-    #[serde(rename = "gidMappings")]
+    #[serde(rename = "gidMappings", skip_serializing_if = "Option::is_none")]
     pub gid_mappings: Option<Vec<LinuxIdMapping>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct LinuxIdMapping {
+    #[serde(rename = "containerID", alias = "container_id")]
     pub container_id: u32,
+
+    #[serde(rename = "hostID", alias = "host_id")]
     pub host_id: u32,
+
+    #[serde(rename = "size")]
     pub size: u32,
 }
 
@@ -62,4 +346,35 @@ pub struct ContainerState {
 
     #[serde(rename = "pid")]
     pub pid: Option<u32>,
+
+    // the start time of pid in clock ticks after boot, tells the container process apart from a later one reusing its pid
+    #[serde(rename = "pidStartTime")]
+    pub pid_start_time: Option<u64>,
+
+    #[serde(rename = "consoleSocket")]
+    pub console_socket: Option<String>,
+
+    // where frogc mounted the rootfs it assembled, unset when the spec gives the rootfs as a path
+    #[serde(rename = "rootfsMount")]
+    pub rootfs_mount: Option<String>,
+
+    // the OCI bundle the container was created from
+    // such containers follow the OCI lifecycle: create sets up the process, which waits in the created state until start
+    #[serde(rename = "bundle")]
+    pub bundle: Option<String>,
+
+    #[serde(rename = "createdAt")]
+    pub created_at: Option<DateTime<Utc>>,
+
+    #[serde(rename = "exitCode")]
+    pub exit_code: Option<i32>,
+
+    #[serde(rename = "exitSignal")]
+    pub exit_signal: Option<String>,
+
+    #[serde(rename = "oomKilled")]
+    pub oom_killed: Option<bool>,
+
+    #[serde(rename = "finishedAt")]
+    pub finished_at: Option<DateTime<Utc>>,
 }
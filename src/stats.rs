@@ -0,0 +1,161 @@
+use crate::cgroup::Cgroup;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+
+// the envelope printed by the stats and events commands, one JSON document per line
+#[derive(Debug, Serialize)]
+pub struct Event<T: Serialize> {
+    #[serde(rename = "type")]
+    pub event_type: &'static str,
+
+    #[serde(rename = "id")]
+    pub id: String,
+
+    #[serde(rename = "time")]
+    pub time: DateTime<Utc>,
+
+    #[serde(rename = "data", skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+}
+
+impl<T: Serialize> Event<T> {
+    pub fn new(event_type: &'static str, id: &str, data: Option<T>) -> Self {
+        Self {
+            event_type,
+            id: id.to_string(),
+            time: Utc::now(),
+            data,
+        }
+    }
+
+    pub fn print(&self) -> serde_json::Result<()> {
+        println!("{}", serde_json::to_string(self)?);
+        Ok(())
+    }
+}
+
+// a point in time snapshot of the container's cgroup counters
+// controllers that are not enabled for the cgroup are left out
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    #[serde(rename = "cpu", skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<BTreeMap<String, u64>>,
+
+    #[serde(rename = "memory", skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryStats>,
+
+    #[serde(rename = "pids", skip_serializing_if = "Option::is_none")]
+    pub pids: Option<PidsStats>,
+
+    #[serde(rename = "io", skip_serializing_if = "Option::is_none")]
+    pub io: Option<Vec<IoStats>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemoryStats {
+    #[serde(rename = "current")]
+    pub current: u64,
+
+    #[serde(rename = "oomKills")]
+    pub oom_kills: u64,
+
+    #[serde(rename = "stat")]
+    pub stat: BTreeMap<String, u64>,
+
+    #[serde(rename = "events")]
+    pub events: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PidsStats {
+    #[serde(rename = "current")]
+    pub current: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct IoStats {
+    #[serde(rename = "major")]
+    pub major: u32,
+
+    #[serde(rename = "minor")]
+    pub minor: u32,
+
+    #[serde(rename = "stat")]
+    pub stat: BTreeMap<String, u64>,
+}
+
+pub fn collect(cgroup: &Cgroup) -> io::Result<Stats> {
+    let cpu = read_optional(cgroup, "cpu.stat")?.map(|s| parse_flat_keyed_map(&s));
+    let pids = read_optional(cgroup, "pids.current")?
+        .map(|s| PidsStats { current: parse_single(&s) });
+    let io = read_optional(cgroup, "io.stat")?.map(|s| parse_io_stat(&s));
+
+    let memory = match read_optional(cgroup, "memory.current")? {
+        Some(current) => {
+            let events = parse_flat_keyed_map(&cgroup.read("memory.events")?);
+            Some(MemoryStats {
+                current: parse_single(&current),
+                oom_kills: events.get("oom_kill").copied().unwrap_or(0),
+                stat: parse_flat_keyed_map(&cgroup.read("memory.stat")?),
+                events,
+            })
+        }
+        None => None,
+    };
+
+    Ok(Stats { cpu, memory, pids, io })
+}
+
+// returns the number of processes killed by the OOM killer in the cgroup, as reported by memory.events
+pub fn oom_kills(cgroup: &Cgroup) -> io::Result<u64> {
+    Ok(read_optional(cgroup, "memory.events")?
+        .and_then(|s| parse_flat_keyed_map(&s).get("oom_kill").copied())
+        .unwrap_or(0))
+}
+
+fn read_optional(cgroup: &Cgroup, file: &str) -> io::Result<Option<String>> {
+    match cgroup.read(file) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn parse_single(content: &str) -> u64 {
+    content.trim().parse().unwrap_or(0)
+}
+
+fn parse_flat_keyed_map(content: &str) -> BTreeMap<String, u64> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (k, v) = line.split_once(' ')?;
+            Some((k.to_string(), v.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+// io.stat has one line per device: "8:0 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=0 dios=0"
+fn parse_io_stat(content: &str) -> Vec<IoStats> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (major, minor) = fields.next()?.split_once(':')?;
+            let stat = fields
+                .filter_map(|field| {
+                    let (k, v) = field.split_once('=')?;
+                    Some((k.to_string(), v.parse().ok()?))
+                })
+                .collect();
+
+            Some(IoStats {
+                major: major.parse().ok()?,
+                minor: minor.parse().ok()?,
+                stat,
+            })
+        })
+        .collect()
+}
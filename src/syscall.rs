@@ -1,9 +1,11 @@
 use nix::errno::Errno;
 use nix::fcntl::AtFlags;
-use nix::libc::{c_int, c_ulong, EBADF};
+use nix::libc::{c_int, c_uint, c_ulong, EBADF};
 use nix::{NixPath, libc};
-use std::os::fd::{AsRawFd, BorrowedFd};
+use std::ffi::CStr;
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
 use nix::mount::MsFlags;
+use nix::unistd::Pid;
 
 macro_rules! libc_bitflags {
     (
@@ -52,6 +54,7 @@ pub struct MountAttr<'a> {
     pub userns_fd: BorrowedFd<'a>,
 }
 
+#[repr(C)]
 struct MountAttrInternal {
     attr_set: c_ulong,
     attr_clr: c_ulong,
@@ -76,16 +79,216 @@ pub fn mount_setattr<P1: ?Sized + NixPath>(
     flags: AtFlags,
     mount_attr: &MountAttr,
 ) -> nix::Result<()> {
-    let res = unsafe {
+    let mount_attr = MountAttrInternal::from(mount_attr);
+    let res = path.with_nix_path(|cstr| unsafe {
         libc::syscall(
             libc::SYS_mount_setattr,
             dirfd.map(|x| x.as_raw_fd() as c_int).unwrap_or(-EBADF),
-            path,
-            flags,
-            &MountAttrInternal::from(mount_attr) as *const MountAttrInternal,
+            cstr.as_ptr(),
+            flags.bits(),
+            &mount_attr as *const MountAttrInternal,
             size_of::<MountAttrInternal>(),
         )
+    })?;
+
+    Errno::result(res).map(drop)
+}
+
+// returns a file descriptor referring to the process, it becomes readable once the process exits
+pub fn pidfd_open(pid: Pid) -> nix::Result<OwnedFd> {
+    let res = unsafe { libc::syscall(libc::SYS_pidfd_open, pid.as_raw(), 0) };
+
+    Errno::result(res).map(|fd| unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+// nix only wraps sethostname
+pub fn setdomainname(name: &str) -> nix::Result<()> {
+    let res = unsafe { libc::setdomainname(name.as_ptr().cast(), name.len()) };
+
+    Errno::result(res).map(drop)
+}
+
+const MOVE_MOUNT_F_EMPTY_PATH: c_uint = 0x00000004;
+
+// detaches a copy of the mount at path, which move_mount can attach somewhere else
+// unlike a bind mount by path, the source can be reached through a directory outside our root
+// recursive copies the mounts below path as well, like an rbind
+pub fn open_tree<P: ?Sized + NixPath>(dirfd: BorrowedFd, path: &P, recursive: bool) -> nix::Result<OwnedFd> {
+    let mut flags = libc::OPEN_TREE_CLONE | libc::O_CLOEXEC as c_uint;
+    if recursive {
+        flags |= libc::AT_RECURSIVE as c_uint;
+    }
+    let res = path.with_nix_path(|cstr| unsafe {
+        libc::syscall(libc::SYS_open_tree, dirfd.as_raw_fd(), cstr.as_ptr(), flags)
+    })?;
+
+    Errno::result(res).map(|fd| unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+// attaches a mount returned by open_tree at path
+pub fn move_mount<P: ?Sized + NixPath>(mount: BorrowedFd, path: &P) -> nix::Result<()> {
+    let res = path.with_nix_path(|cstr| unsafe {
+        libc::syscall(
+            libc::SYS_move_mount,
+            mount.as_raw_fd(),
+            c"".as_ptr(),
+            libc::AT_FDCWD,
+            cstr.as_ptr(),
+            MOVE_MOUNT_F_EMPTY_PATH,
+        )
+    })?;
+
+    Errno::result(res).map(drop)
+}
+
+const BPF_PROG_LOAD: c_int = 5;
+const BPF_PROG_ATTACH: c_int = 8;
+pub const BPF_PROG_TYPE_CGROUP_DEVICE: u32 = 15;
+pub const BPF_CGROUP_DEVICE: u32 = 6;
+
+// a single instruction of an eBPF program, the registers are packed into one byte
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BpfInsn {
+    pub code: u8,
+    pub regs: u8,
+    pub off: i16,
+    pub imm: i32,
+}
+
+// the leading fields of union bpf_attr for BPF_PROG_LOAD, the kernel zero fills the rest
+#[repr(C)]
+#[derive(Default)]
+struct BpfProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct BpfProgAttachAttr {
+    target_fd: u32,
+    attach_bpf_fd: u32,
+    attach_type: u32,
+    attach_flags: u32,
+}
+
+// loads a program into the kernel, it stays loaded as long as the fd or an attachment refers to it
+pub fn bpf_prog_load(prog_type: u32, insns: &[BpfInsn], license: &CStr) -> nix::Result<OwnedFd> {
+    let attr = BpfProgLoadAttr {
+        prog_type,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: license.as_ptr() as u64,
+        ..Default::default()
+    };
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_PROG_LOAD,
+            &attr as *const BpfProgLoadAttr,
+            size_of::<BpfProgLoadAttr>(),
+        )
+    };
+
+    Errno::result(res).map(|fd| unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+// without flags the program replaces whatever was attached before and descendants cannot attach their own
+pub fn bpf_prog_attach(target: BorrowedFd, prog: BorrowedFd, attach_type: u32) -> nix::Result<()> {
+    let attr = BpfProgAttachAttr {
+        target_fd: target.as_raw_fd() as u32,
+        attach_bpf_fd: prog.as_raw_fd() as u32,
+        attach_type,
+        ..Default::default()
+    };
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_PROG_ATTACH,
+            &attr as *const BpfProgAttachAttr,
+            size_of::<BpfProgAttachAttr>(),
+        )
     };
 
     Errno::result(res).map(drop)
 }
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x20080522;
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: c_int,
+}
+
+// version 3 splits every set into two 32 bit halves
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+// the capability sets of a thread, bit n stands for capability number n
+#[derive(Debug, Clone, Copy)]
+pub struct CapSets {
+    pub effective: u64,
+    pub permitted: u64,
+    pub inheritable: u64,
+}
+
+// the capability sets of the calling thread
+pub fn capget() -> nix::Result<CapSets> {
+    let mut header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let mut data = [CapUserData::default(); 2];
+    let res = unsafe { libc::syscall(libc::SYS_capget, &mut header as *mut CapUserHeader, data.as_mut_ptr()) };
+
+    Errno::result(res).map(|_| CapSets {
+        effective: data[0].effective as u64 | (data[1].effective as u64) << 32,
+        permitted: data[0].permitted as u64 | (data[1].permitted as u64) << 32,
+        inheritable: data[0].inheritable as u64 | (data[1].inheritable as u64) << 32,
+    })
+}
+
+// sets the capability sets of the calling thread, without CAP_SETPCAP they can only shrink
+pub fn capset(sets: &CapSets) -> nix::Result<()> {
+    let mut header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let half = |shift: u32| CapUserData {
+        effective: (sets.effective >> shift) as u32,
+        permitted: (sets.permitted >> shift) as u32,
+        inheritable: (sets.inheritable >> shift) as u32,
+    };
+    let data = [half(0), half(32)];
+    let res = unsafe { libc::syscall(libc::SYS_capset, &mut header as *mut CapUserHeader, data.as_ptr()) };
+
+    Errno::result(res).map(drop)
+}
+
+// whether the capability is in the bounding set, EINVAL for numbers the kernel does not know
+pub fn capbset_read(cap: u32) -> nix::Result<bool> {
+    let res = unsafe { libc::prctl(libc::PR_CAPBSET_READ, cap as c_ulong, 0, 0, 0) };
+
+    Errno::result(res).map(|res| res == 1)
+}
+
+// removes the capability from the bounding set, nothing the thread executes afterwards can gain it
+pub fn capbset_drop(cap: u32) -> nix::Result<()> {
+    let res = unsafe { libc::prctl(libc::PR_CAPBSET_DROP, cap as c_ulong, 0, 0, 0) };
+
+    Errno::result(res).map(drop)
+}
@@ -0,0 +1,50 @@
+use crate::namespaces;
+use crate::spec::ContainerSpec;
+use std::path::Path;
+use std::{fs, io};
+
+// the sysctls the kernel keeps per IPC namespace, everything below net. is per network namespace
+// all other sysctls are global, setting them from a container would change the host
+const IPC_PREFIXES: [&str; 4] = ["kernel.msg", "kernel.shm", "kernel.sem", "fs.mqueue."];
+
+// checks that the key is namespaced and the container has its own namespace of that type
+pub fn check(spec: &ContainerSpec, key: &str) -> Result<(), String> {
+    // the name becomes a path below /proc/sys, it must not be able to point anywhere else
+    if path(key).split('/').any(|c| c.is_empty() || c == "." || c == "..") {
+        return Err("is not a valid sysctl name".to_string());
+    }
+
+    let ns_type = if key.starts_with("net.") {
+        "network"
+    } else if IPC_PREFIXES.iter().any(|prefix| key.starts_with(prefix)) {
+        "ipc"
+    } else {
+        return Err("is not namespaced, it can only be set on the host".to_string());
+    };
+    if !namespaces::is_private(spec, ns_type) {
+        return Err(format!("can only be set with a private {} namespace", ns_type));
+    }
+
+    Ok(())
+}
+
+// must be called once the container's /proc is mounted and before /proc/sys is made read-only
+pub fn apply(spec: &ContainerSpec) -> io::Result<()> {
+    for (key, value) in spec.sysctl.iter().flatten() {
+        fs::write(Path::new("/proc/sys").join(path(key)), value)
+            .map_err(|e| io::Error::new(e.kind(), format!("setting {}: {}", key, e)))?;
+    }
+
+    Ok(())
+}
+
+// the dots of the name separate directories, a slash stands for a dot within one, like an interface eth0.100
+fn path(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            '.' => '/',
+            '/' => '.',
+            c => c,
+        })
+        .collect()
+}
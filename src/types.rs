@@ -1,6 +1,5 @@
 use std::os::fd::{BorrowedFd, RawFd};
-use bitflags::Flags;
-use nix::libc::{EBADF, MS_ASYNC};
+use nix::libc::EBADF;
 use nix::mount::MsFlags;
 use crate::spec;
 use crate::syscall::{MountAttr, MountAttrFlags};
@@ -11,95 +10,125 @@ pub struct Mount<'a> {
     pub fs_type: String,
     pub flags: MsFlags,
     pub mount_attr: MountAttr<'a>,
+    // with a propagation flag mount(2) only changes the propagation of an existing mount, so it is set by a call of its own
+    pub propagation: Option<MsFlags>,
     pub options: Option<String>,
     pub tmp_copy_up: bool,
 }
 
+// what a mount option does, options that are not in the table are passed to the filesystem
+// the table is also what `frogc features` reports, so it has to list every option we handle
+#[derive(Clone, Copy)]
+pub enum MountOption {
+    Set(MsFlags),
+    Clear(MsFlags),
+    Propagation(MsFlags),
+    RecursiveSet(MountAttrFlags),
+    RecursiveClear(MountAttrFlags),
+    TmpCopyUp,
+    // accepted without effect
+    Ignored,
+    // recognized, but not implemented yet, so not advertised as supported
+    Unsupported,
+}
+
+pub const MOUNT_OPTIONS: [(&str, MountOption); 61] = [
+    ("async", MountOption::Clear(MsFlags::MS_SYNCHRONOUS)),
+    ("atime", MountOption::Clear(MsFlags::MS_NOATIME)),
+    ("bind", MountOption::Set(MsFlags::MS_BIND)),
+    ("defaults", MountOption::Ignored),
+    ("dev", MountOption::Clear(MsFlags::MS_NODEV)),
+    ("diratime", MountOption::Clear(MsFlags::MS_NODIRATIME)),
+    ("dirsync", MountOption::Set(MsFlags::MS_DIRSYNC)),
+    ("exec", MountOption::Clear(MsFlags::MS_NOEXEC)),
+    ("iversion", MountOption::Set(MsFlags::MS_I_VERSION)),
+    ("lazytime", MountOption::Set(MsFlags::MS_LAZYTIME)),
+    ("loud", MountOption::Clear(MsFlags::MS_SILENT)),
+    ("mand", MountOption::Set(MsFlags::MS_MANDLOCK)),
+    ("noatime", MountOption::Set(MsFlags::MS_NOATIME)),
+    ("nodev", MountOption::Set(MsFlags::MS_NODEV)),
+    ("nodiratime", MountOption::Set(MsFlags::MS_NODIRATIME)),
+    ("noexec", MountOption::Set(MsFlags::MS_NOEXEC)),
+    ("noiversion", MountOption::Clear(MsFlags::MS_I_VERSION)),
+    ("nolazytime", MountOption::Clear(MsFlags::MS_LAZYTIME)),
+    ("nomand", MountOption::Clear(MsFlags::MS_MANDLOCK)),
+    ("norelatime", MountOption::Clear(MsFlags::MS_RELATIME)),
+    ("nostrictatime", MountOption::Clear(MsFlags::MS_STRICTATIME)),
+    ("nosuid", MountOption::Set(MsFlags::MS_NOSUID)),
+    ("nosymfollow", MountOption::Unsupported),
+    ("private", MountOption::Propagation(MsFlags::MS_PRIVATE)),
+    ("ratime", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR__ATIME)),
+    ("rbind", MountOption::Set(MsFlags::MS_BIND.union(MsFlags::MS_REC))),
+    ("rdev", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_NODEV)),
+    ("rdiratime", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_NODIRATIME)),
+    ("relatime", MountOption::Set(MsFlags::MS_RELATIME)),
+    ("remount", MountOption::Set(MsFlags::MS_REMOUNT)),
+    ("rexec", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_NOEXEC)),
+    ("rnoatime", MountOption::Set(MsFlags::MS_NOATIME)),
+    ("rnodiratime", MountOption::RecursiveSet(MountAttrFlags::MOUNT_ATTR_NODIRATIME)),
+    ("rnoexec", MountOption::RecursiveSet(MountAttrFlags::MOUNT_ATTR_NOEXEC)),
+    ("rnorelatime", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_RELATIME)),
+    ("rnostrictatime", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_STRICTATIME)),
+    ("rnosuid", MountOption::RecursiveSet(MountAttrFlags::MOUNT_ATTR_NOSUID)),
+    ("rnosymfollow", MountOption::RecursiveSet(MountAttrFlags::MOUNT_ATTR_NOSYMFOLLOW)),
+    ("ro", MountOption::Set(MsFlags::MS_RDONLY)),
+    ("rprivate", MountOption::Propagation(MsFlags::MS_PRIVATE.union(MsFlags::MS_REC))),
+    ("rrelatime", MountOption::RecursiveSet(MountAttrFlags::MOUNT_ATTR_RELATIME)),
+    ("rro", MountOption::RecursiveSet(MountAttrFlags::MOUNT_ATTR_RDONLY)),
+    ("rrw", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_RDONLY)),
+    ("rshared", MountOption::Propagation(MsFlags::MS_SHARED.union(MsFlags::MS_REC))),
+    ("rslave", MountOption::Propagation(MsFlags::MS_SLAVE.union(MsFlags::MS_REC))),
+    ("rstrictatime", MountOption::RecursiveSet(MountAttrFlags::MOUNT_ATTR_STRICTATIME)),
+    ("rsuid", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_NOSUID)),
+    ("rsymfollow", MountOption::RecursiveClear(MountAttrFlags::MOUNT_ATTR_NOSYMFOLLOW)),
+    ("runbindable", MountOption::Propagation(MsFlags::MS_UNBINDABLE.union(MsFlags::MS_REC))),
+    ("rw", MountOption::Clear(MsFlags::MS_RDONLY)),
+    ("shared", MountOption::Propagation(MsFlags::MS_SHARED)),
+    ("silent", MountOption::Set(MsFlags::MS_SILENT)),
+    ("slave", MountOption::Propagation(MsFlags::MS_SLAVE)),
+    ("strictatime", MountOption::Set(MsFlags::MS_STRICTATIME)),
+    ("suid", MountOption::Clear(MsFlags::MS_NOSUID)),
+    ("symfollow", MountOption::Unsupported),
+    ("sync", MountOption::Set(MsFlags::MS_SYNCHRONOUS)),
+    ("tmpcopyup", MountOption::TmpCopyUp),
+    ("unbindable", MountOption::Propagation(MsFlags::MS_UNBINDABLE)),
+    ("idmap", MountOption::Unsupported),
+    ("ridmap", MountOption::Unsupported),
+];
+
+impl Mount<'_> {
+    pub fn is_bind(&self) -> bool {
+        self.fs_type == "bind" || self.flags.contains(MsFlags::MS_BIND)
+    }
+}
+
+pub fn mount_option(name: &str) -> Option<MountOption> {
+    MOUNT_OPTIONS
+        .iter()
+        .find(|(option, _)| *option == name)
+        .map(|(_, effect)| *effect)
+}
+
 impl From<&spec::Mount> for Mount<'_> {
     fn from(mount: &spec::Mount) -> Self {
         let mut tmp_copy_up = false;
         let mut mount_flags = MsFlags::empty();
-        let mut propagation_flags = MsFlags::empty();
+        let mut propagation = None;
         let mut recursive_flags = MountAttrFlags::empty();
         let mut recursive_clear_flags = MountAttrFlags::empty();
         let mut options : Vec<String> = Vec::new();
 
         for option in &mount.options {
-            match option.as_str() {
-                "async" => mount_flags.set(MsFlags::MS_BIND, false),
-                "atime" => mount_flags.set(MsFlags::MS_BIND, false),
-                "bind" => mount_flags.set(MsFlags::MS_BIND, true),
-                "defaults" => (), // ignored for now
-                "dev" => mount_flags.set(MsFlags::MS_NODEV, false),
-                "diratime" => mount_flags.set(MsFlags::MS_NODIRATIME, false),
-                "dirsync" => mount_flags.set(MsFlags::MS_DIRSYNC, true),
-                "exec" => mount_flags.set(MsFlags::MS_NOEXEC, false),
-                "iversion" => mount_flags.set(MsFlags::MS_I_VERSION, true),
-                "lazytime" => mount_flags.set(MsFlags::MS_LAZYTIME, true),
-                "loud" => mount_flags.set(MsFlags::MS_SILENT, false),
-                "mand" => mount_flags.set(MsFlags::MS_MANDLOCK, true),
-                "noatime" => mount_flags.set(MsFlags::MS_NOATIME, true),
-                "nodev" => mount_flags.set(MsFlags::MS_NODEV, true),
-                "nodiratime" => mount_flags.set(MsFlags::MS_NODIRATIME, true),
-                "noexec" => mount_flags.set(MsFlags::MS_NOEXEC, true),
-                "noiversion" => mount_flags.set(MsFlags::MS_I_VERSION, false),
-                "nolazytime" => mount_flags.set(MsFlags::MS_LAZYTIME, false),
-                "nomand" => mount_flags.set(MsFlags::MS_MANDLOCK, false),
-                "norelatime" => mount_flags.set(MsFlags::MS_RELATIME, false),
-                "nostrictatime" => mount_flags.set(MsFlags::MS_STRICTATIME, false),
-                "nosuid" => mount_flags.set(MsFlags::MS_NOSUID, true),
-                "nosymfollow" => (), // SHOULD => we dont support this option for now
-                "private" => mount_flags.set(MsFlags::MS_PRIVATE, true),
-                "ratime" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR__ATIME, true),
-                "rbind" => {
-                    mount_flags.set(MsFlags::MS_BIND, true);
-                    mount_flags.set(MsFlags::MS_REC, true);
-                }
-                "rdev" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_NODEV, true),
-                "rdiratime" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_NODIRATIME, true),
-                "relatime" => mount_flags.set(MsFlags::MS_RELATIME, true),
-                "remount" => mount_flags.set(MsFlags::MS_REMOUNT, true),
-                "rexec" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_NOEXEC, true),
-                "rnoatime" => mount_flags.set(MsFlags::MS_NOATIME, true),
-                "rnodiratime" => recursive_flags.set(MountAttrFlags::MOUNT_ATTR_NODIRATIME, true),
-                "rnoexec" => recursive_flags.set(MountAttrFlags::MOUNT_ATTR_NOEXEC, true),
-                "rnorelatime" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_RELATIME, true),
-                "rnostrictatime" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_STRICTATIME, true),
-                "rnosuid" => recursive_flags.set(MountAttrFlags::MOUNT_ATTR_NOSUID, true),
-                "rnosymfollow" => recursive_flags.set(MountAttrFlags::MOUNT_ATTR_NOSYMFOLLOW, true),
-                "ro" => mount_flags.set(MsFlags::MS_RDONLY, true),
-                "rprivate" => {
-                    propagation_flags.set(MsFlags::MS_PRIVATE, true);
-                    propagation_flags.set(MsFlags::MS_REC, true);
-                }
-                "rrelatime" => recursive_flags.set(MountAttrFlags::MOUNT_ATTR_RELATIME, true),
-                "rro" => recursive_flags.set(MountAttrFlags::MOUNT_ATTR_RDONLY, true),
-                "rrw" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_RDONLY, true),
-                "rshared" => {
-                    propagation_flags.set(MsFlags::MS_SHARED, true);
-                    propagation_flags.set(MsFlags::MS_REC, true);
-                }
-                "rslave" => {
-                    propagation_flags.set(MsFlags::MS_SLAVE, true);
-                    propagation_flags.set(MsFlags::MS_REC, true);
-                }
-                "rstrictatime" => recursive_flags.set(MountAttrFlags::MOUNT_ATTR_STRICTATIME, true),
-                "rsuid" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_NOSUID, true),
-                "rsymfollow" => recursive_clear_flags.set(MountAttrFlags::MOUNT_ATTR_NOSYMFOLLOW, true),
-                "runbindable" => propagation_flags.set(MsFlags::MS_UNBINDABLE, true),
-                "rw" => mount_flags.set(MsFlags::MS_RDONLY, false),
-                "shared" => propagation_flags.set(MsFlags::MS_SHARED, true),
-                "silent" => mount_flags.set(MsFlags::MS_SILENT, true),
-                "slave" => propagation_flags.set(MsFlags::MS_SLAVE, true),
-                "strictatime" => mount_flags.set(MsFlags::MS_STRICTATIME, true),
-                "suid" => mount_flags.set(MsFlags::MS_NOSUID, false),
-                "symfollow" => (), // ignored for now
-                "sync" => mount_flags.set(MsFlags::MS_SYNCHRONOUS, true),
-                "tmpcopyup" => tmp_copy_up = true,
-                "unbindable" => mount_flags.set(MsFlags::MS_UNBINDABLE, true),
-                "idmap" => (), // ignored for now
-                "ridmap" => (),
-                _ => options.push(option.clone()), // unknown options are supposed to be treated as filesystem specific options
+            match mount_option(option) {
+                Some(MountOption::Set(flags)) => mount_flags.insert(flags),
+                Some(MountOption::Clear(flags)) => mount_flags.remove(flags),
+                // a mount has a single propagation type, the last one given wins
+                Some(MountOption::Propagation(flags)) => propagation = Some(flags),
+                Some(MountOption::RecursiveSet(flags)) => recursive_flags.insert(flags),
+                Some(MountOption::RecursiveClear(flags)) => recursive_clear_flags.insert(flags),
+                Some(MountOption::TmpCopyUp) => tmp_copy_up = true,
+                Some(MountOption::Ignored | MountOption::Unsupported) => (),
+                None => options.push(option.clone()), // unknown options are supposed to be treated as filesystem specific options
             }
         }
 
@@ -108,13 +137,14 @@ impl From<&spec::Mount> for Mount<'_> {
                 source: mount.source.clone(),
                 destination: mount.destination.clone(),
                 fs_type: mount.fs_type.clone(),
-                flags: MsFlags::empty(),
+                flags: mount_flags,
                 mount_attr: MountAttr {
                     attr_clr: recursive_clear_flags,
                     attr_set: recursive_flags,
-                    propagation: propagation_flags,
+                    propagation: MsFlags::empty(),
                     userns_fd: BorrowedFd::borrow_raw(RawFd::from(-EBADF)),
                 },
+                propagation,
                 options: if options.is_empty() { None } else { Some(options.join(",")) },
                 tmp_copy_up,
             }
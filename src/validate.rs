@@ -0,0 +1,397 @@
+use crate::capabilities;
+use crate::errors::ContainerError;
+use crate::namespaces;
+use crate::sysctl;
+use crate::namespaces::NAMESPACE_TYPES;
+use crate::spec::{ContainerSpec, Device, DeviceRule, Hook, LinuxIdMapping, Mount, Rootfs};
+use crate::types::{mount_option, MountOption};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::path::Path;
+
+// the limits the kernel accepts for cpu.max
+const CPU_PERIOD_MIN: u64 = 1_000;
+const CPU_PERIOD_MAX: u64 = 1_000_000;
+const HOSTNAME_MAX: usize = 64;
+
+// checks the whole spec before anything is created for it
+// every problem is collected with the JSON path of the offending field, so they can all be fixed at once
+pub fn validate(spec: &ContainerSpec) -> Result<(), ContainerError> {
+    let mut v = Validator::default();
+
+    v.rootfs(spec);
+    if let Some(work_dir) = &spec.work_dir {
+        v.absolute("workDir", work_dir);
+    }
+
+    if spec.cmd.is_empty() {
+        v.problem("cmd", "must not be empty");
+    } else if spec.cmd[0].is_empty() {
+        v.problem("cmd[0]", "must not be empty");
+    }
+    v.strings("cmd", &spec.cmd);
+
+    if let Some(env) = &spec.env {
+        v.strings("env", env);
+        for (i, var) in env.iter().enumerate() {
+            if !var.contains('=') {
+                v.problem(&format!("env[{}]", i), "must have the form NAME=VALUE");
+            }
+        }
+    }
+
+    for (i, cap) in spec.capabilities.iter().flatten().enumerate() {
+        if !capabilities::is_known(cap) {
+            v.problem(&format!("capabilities[{}]", i), &format!("unknown capability {:?}", cap));
+        }
+    }
+
+    for (field, name) in [("hostname", &spec.hostname), ("domainname", &spec.domainname)] {
+        let Some(name) = name else {
+            continue;
+        };
+        if name.len() > HOSTNAME_MAX {
+            v.problem(field, &format!("must not be longer than {} bytes", HOSTNAME_MAX));
+        }
+        // a shared or joined UTS namespace keeps its names, setting them would rename the host or another container
+        if !namespaces::is_private(spec, "uts") {
+            v.problem(field, "can only be set with a private uts namespace");
+        }
+    }
+
+    v.resources(spec);
+    v.namespaces(spec);
+
+    for (i, m) in spec.mounts.iter().enumerate() {
+        v.mount(&format!("mounts[{}]", i), m);
+    }
+
+    for (i, device) in spec.devices.iter().flatten().enumerate() {
+        v.device(&format!("devices[{}]", i), device);
+    }
+
+    for (i, rule) in spec.device_rules.iter().flatten().enumerate() {
+        v.device_rule(&format!("deviceRules[{}]", i), rule);
+    }
+
+    for (i, path) in spec.masked_paths.iter().flatten().enumerate() {
+        v.absolute(&format!("maskedPaths[{}]", i), path);
+    }
+    for (i, path) in spec.readonly_paths.iter().flatten().enumerate() {
+        v.absolute(&format!("readonlyPaths[{}]", i), path);
+    }
+
+    for key in spec.sysctl.iter().flatten().map(|(key, _)| key) {
+        if let Err(e) = sysctl::check(spec, key) {
+            v.problem(&format!("sysctl.{}", key), &e);
+        }
+    }
+
+    if let Some(dns) = &spec.dns {
+        for (i, server) in dns.servers.iter().flatten().enumerate() {
+            v.ip(&format!("dns.servers[{}]", i), server);
+        }
+        v.words("dns.search", dns.search.as_deref().unwrap_or_default());
+        v.words("dns.options", dns.options.as_deref().unwrap_or_default());
+    }
+
+    for (i, extra) in spec.extra_hosts.iter().flatten().enumerate() {
+        let path = format!("extraHosts[{}]", i);
+        v.ip(&format!("{}.ip", path), &extra.ip);
+        if extra.hostnames.is_empty() {
+            v.problem(&format!("{}.hostnames", path), "must not be empty");
+        }
+        v.words(&format!("{}.hostnames", path), &extra.hostnames);
+    }
+
+    if let Some(log) = &spec.log {
+        if log.max_size == Some(0) {
+            v.problem("log.maxSize", "must be greater than 0");
+        }
+        if log.max_files == Some(0) {
+            v.problem("log.maxFiles", "must be greater than 0");
+        }
+    }
+
+    if let Some(hooks) = &spec.hooks {
+        let groups = [
+            ("prestart", &hooks.prestart),
+            ("createRuntime", &hooks.create_runtime),
+            ("createContainer", &hooks.create_container),
+            ("startContainer", &hooks.start_container),
+            ("poststart", &hooks.poststart),
+            ("poststop", &hooks.poststop),
+        ];
+        for (hook_type, group) in groups {
+            for (i, hook) in group.iter().flatten().enumerate() {
+                v.hook(&format!("hooks.{}[{}]", hook_type, i), hook);
+            }
+        }
+    }
+
+    v.into_result()
+}
+
+#[derive(Default)]
+struct Validator {
+    problems: Vec<String>,
+}
+
+impl Validator {
+    fn problem(&mut self, path: &str, msg: &str) {
+        self.problems.push(format!("{}: {}", path, msg));
+    }
+
+    fn into_result(self) -> Result<(), ContainerError> {
+        if self.problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ContainerError::InvalidSpec(self.problems))
+        }
+    }
+
+    fn absolute(&mut self, path: &str, value: &str) {
+        if !value.starts_with('/') {
+            self.problem(path, &format!("{:?} must be an absolute path", value));
+        }
+    }
+
+    // the strings end up as C strings, which cannot hold NUL bytes
+    fn strings(&mut self, path: &str, values: &[String]) {
+        for (i, value) in values.iter().enumerate() {
+            if value.contains('\0') {
+                self.problem(&format!("{}[{}]", path, i), "must not contain NUL bytes");
+            }
+        }
+    }
+
+    fn ip(&mut self, path: &str, value: &str) {
+        if value.parse::<IpAddr>().is_err() {
+            self.problem(path, &format!("{:?} is not an IP address", value));
+        }
+    }
+
+    // the values are written into a line of a file under /etc, separated by spaces
+    fn words(&mut self, path: &str, values: &[String]) {
+        for (i, value) in values.iter().enumerate() {
+            if value.is_empty() || value.contains(char::is_whitespace) || value.contains('#') {
+                self.problem(&format!("{}[{}]", path, i), &format!("{:?} must be a single word", value));
+            }
+        }
+    }
+
+    fn directory(&mut self, path: &str, dir: &str) {
+        if !dir.starts_with('/') {
+            self.absolute(path, dir);
+        } else if !Path::new(dir).is_dir() {
+            self.problem(path, &format!("{} is not a directory", dir));
+        }
+    }
+
+    fn rootfs(&mut self, spec: &ContainerSpec) {
+        let overlay = match &spec.rootfs {
+            Rootfs::Path(path) => return self.directory("rootfs", path),
+            Rootfs::Overlay(overlay) => overlay,
+        };
+
+        if overlay.lower_dirs.is_empty() {
+            self.problem("rootfs.lowerDirs", "must not be empty");
+        }
+        for (i, dir) in overlay.lower_dirs.iter().enumerate() {
+            self.overlay_dir(&format!("rootfs.lowerDirs[{}]", i), dir);
+        }
+
+        match (&overlay.upper_dir, &overlay.work_dir) {
+            (Some(upper_dir), Some(work_dir)) => {
+                self.overlay_dir("rootfs.upperDir", upper_dir);
+                self.overlay_dir("rootfs.workDir", work_dir);
+            }
+            (Some(_), None) => self.problem("rootfs.workDir", "is required with upperDir"),
+            (None, Some(_)) => self.problem("rootfs.upperDir", "is required with workDir"),
+            (None, None) => {
+                if overlay.lower_dirs.len() < 2 {
+                    self.problem("rootfs.lowerDirs", "needs at least two entries without upperDir");
+                }
+                if overlay.volatile == Some(true) {
+                    self.problem("rootfs.volatile", "needs upperDir");
+                }
+            }
+        }
+
+        if overlay.idmap_lower_dirs == Some(true) && !namespaces::is_private(spec, "user") {
+            self.problem("rootfs.idmapLowerDirs", "needs a user namespace");
+        }
+    }
+
+    fn overlay_dir(&mut self, path: &str, dir: &str) {
+        self.directory(path, dir);
+        // the separators of the overlayfs mount options
+        if dir.contains([':', ',']) {
+            self.problem(path, "must not contain : or ,");
+        }
+    }
+
+    fn resources(&mut self, spec: &ContainerSpec) {
+        if spec.cpu == Some(0) {
+            self.problem("cpu", "must be greater than 0");
+        }
+        if spec.memory == Some(0) {
+            self.problem("memory", "must be greater than 0");
+        }
+        if let Some(quota) = spec.cpu_quota
+            && (quota == 0 || quota < -1)
+        {
+            self.problem("cpuQuota", "must be greater than 0, or -1 for no limit");
+        }
+        if let Some(period) = spec.cpu_period
+            && !(CPU_PERIOD_MIN..=CPU_PERIOD_MAX).contains(&period)
+        {
+            self.problem(
+                "cpuPeriod",
+                &format!("must be between {} and {}", CPU_PERIOD_MIN, CPU_PERIOD_MAX),
+            );
+        }
+        if let Some(pids) = spec.pids
+            && (pids == 0 || pids < -1)
+        {
+            self.problem("pids", "must be greater than 0, or -1 for no limit");
+        }
+    }
+
+    fn namespaces(&mut self, spec: &ContainerSpec) {
+        let Some(namespaces) = &spec.namespaces else {
+            return;
+        };
+
+        let mut seen = HashSet::new();
+        let mut private_mount = false;
+        let mut private_user = false;
+        for (i, ns) in namespaces.iter().enumerate() {
+            let path = format!("namespaces[{}]", i);
+            let ns_type = ns.ns_type.as_str();
+            if !NAMESPACE_TYPES.contains(&ns_type) {
+                self.problem(&format!("{}.type", path), &format!("unknown namespace type {:?}", ns_type));
+                continue;
+            }
+            if !seen.insert(ns_type) {
+                self.problem(&format!("{}.type", path), &format!("{} is listed more than once", ns_type));
+            }
+
+            match &ns.path {
+                None => {
+                    private_mount |= ns_type == "mount";
+                    private_user |= ns_type == "user";
+                }
+                // namespaces.rs can only join these once the process has been cloned
+                Some(_) if !matches!(ns_type, "uts" | "ipc" | "network" | "cgroup") => {
+                    self.problem(
+                        &format!("{}.path", path),
+                        &format!("joining an existing {} namespace is not supported", ns_type),
+                    );
+                }
+                Some(ns_path) if !Path::new(ns_path).exists() => {
+                    self.problem(&format!("{}.path", path), &format!("{} does not exist", ns_path));
+                }
+                Some(_) => (),
+            }
+        }
+
+        if !private_mount {
+            self.problem("namespaces", "the container needs its own mount namespace");
+        }
+        if private_user {
+            self.id_mappings("uidMappings", spec.uid_mappings.as_deref());
+            self.id_mappings("gidMappings", spec.gid_mappings.as_deref());
+        }
+    }
+
+    fn id_mappings(&mut self, path: &str, mappings: Option<&[LinuxIdMapping]>) {
+        let mappings = mappings.unwrap_or_default();
+        if mappings.is_empty() {
+            self.problem(path, "must not be empty with a user namespace");
+        }
+        for (i, mapping) in mappings.iter().enumerate() {
+            if mapping.size == 0 {
+                self.problem(&format!("{}[{}].size", path, i), "must be greater than 0");
+            }
+        }
+    }
+
+    fn mount(&mut self, path: &str, m: &Mount) {
+        self.absolute(&format!("{}.destination", path), &m.destination);
+
+        let bind = m.fs_type == "bind" || m.options.iter().any(|o| o == "bind" || o == "rbind");
+        if bind && !Path::new(&m.source).exists() {
+            self.problem(&format!("{}.source", path), &format!("{} does not exist", m.source));
+        }
+
+        for (i, option) in m.options.iter().enumerate() {
+            let option_path = format!("{}.options[{}]", path, i);
+            if option.is_empty() {
+                self.problem(&option_path, "must not be empty");
+                continue;
+            }
+
+            match mount_option(option) {
+                Some(MountOption::Unsupported) => {
+                    self.problem(&option_path, &format!("{} is not supported", option));
+                }
+                // the kernel ignores filesystem options on bind mounts, so they cannot do what was intended
+                None if bind => {
+                    self.problem(&option_path, &format!("unknown option {} for a bind mount", option));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    fn device(&mut self, path: &str, device: &Device) {
+        self.absolute(&format!("{}.path", path), &device.path);
+        match device.dev_type.as_str() {
+            "p" => (),
+            "c" | "u" | "b" => {
+                if device.major.is_none() {
+                    self.problem(&format!("{}.major", path), "is required for character and block devices");
+                }
+                if device.minor.is_none() {
+                    self.problem(&format!("{}.minor", path), "is required for character and block devices");
+                }
+            }
+            t => self.problem(
+                &format!("{}.type", path),
+                &format!("unknown device type {:?}, expected c, u, b or p", t),
+            ),
+        }
+    }
+
+    fn device_rule(&mut self, path: &str, rule: &DeviceRule) {
+        if let Some(dev_type) = &rule.dev_type
+            && !matches!(dev_type.as_str(), "a" | "c" | "b")
+        {
+            self.problem(
+                &format!("{}.type", path),
+                &format!("unknown device type {:?}, expected a, c or b", dev_type),
+            );
+        }
+        // the BPF program compares against 32 bit numbers
+        for (field, number) in [("major", rule.major), ("minor", rule.minor)] {
+            if let Some(number) = number
+                && !(-1..=i32::MAX as i64).contains(&number)
+            {
+                self.problem(&format!("{}.{}", path, field), "must be a device number, or -1 for any");
+            }
+        }
+        if let Some(access) = &rule.access
+            && !access.chars().all(|c| matches!(c, 'r' | 'w' | 'm'))
+        {
+            self.problem(&format!("{}.access", path), "must only contain r, w and m");
+        }
+    }
+
+    fn hook(&mut self, path: &str, hook: &Hook) {
+        self.absolute(&format!("{}.path", path), &hook.path);
+        if hook.timeout == Some(0) {
+            self.problem(&format!("{}.timeout", path), "must be greater than 0");
+        }
+    }
+}